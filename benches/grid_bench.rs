@@ -4,14 +4,14 @@ use criterion::{black_box, criterion_group, criterion_main, Criterion};
 fn run_r(universe: &mut UniverseOld, generations: usize) {
     let mut _total: u64 = 0;
     for _ in 0..generations {
-        _total += universe.update() as u64;
+        _total += universe.update().population as u64;
     }
 }
 
 fn run_generations(universe: &mut Universe, generations: usize) {
     let mut _total: u64 = 0;
     for _ in 0..generations {
-        _total += universe.update() as u64;
+        _total += universe.update().population as u64;
     }
 }
 
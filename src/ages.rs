@@ -0,0 +1,141 @@
+//! Tracking when cells were born and died, for colouring cells by their history.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::grid::{GridCoord, Life};
+
+/// Birth generations of the live cells and death generations of recently dead cells.
+///
+/// Each observation compares the live cells with the previous generation, so
+/// costs time proportional to the population.
+pub struct Ages {
+    // Generation of the latest observation, and of the first.
+    generation: usize,
+    start: usize,
+    born: HashMap<(i64, i64), usize>,
+    died: HashMap<(i64, i64), usize>,
+    // Generations the dead cells are remembered for.
+    trail: usize,
+}
+
+impl Ages {
+    /// Start from the cells alive now, remembering dead cells for `trail` generations.
+    pub fn new(universe: &dyn Life, trail: usize) -> Self {
+        let generation = universe.generation();
+        let born = universe
+            .live_cells()
+            .into_iter()
+            .filter_map(|c| match c {
+                GridCoord::Valid(x, y) => Some(((x, y), generation)),
+                _ => None,
+            })
+            .collect();
+
+        Ages {
+            generation,
+            start: generation,
+            born,
+            died: HashMap::new(),
+            trail: trail.max(1),
+        }
+    }
+
+    /// Catch up with the universe's current generation.
+    pub fn observe(&mut self, universe: &dyn Life) {
+        let generation = universe.generation();
+        let live: HashSet<(i64, i64)> = universe
+            .live_cells()
+            .into_iter()
+            .filter_map(|c| match c {
+                GridCoord::Valid(x, y) => Some((x, y)),
+                _ => None,
+            })
+            .collect();
+
+        let died = &mut self.died;
+        self.born.retain(|c, _| {
+            let alive = live.contains(c);
+            if !alive {
+                died.insert(*c, generation);
+            }
+            alive
+        });
+        for c in live {
+            self.born.entry(c).or_insert_with(|| {
+                died.remove(&c);
+                generation
+            });
+        }
+
+        let trail = self.trail;
+        self.died.retain(|_, d| generation - *d < trail);
+        self.generation = generation;
+    }
+
+    /// Generations a live cell has been alive for, 0 if it was born in the latest generation.
+    pub fn age(&self, x: i64, y: i64) -> Option<usize> {
+        self.born.get(&(x, y)).map(|b| self.generation - b)
+    }
+
+    /// Whether a live cell was born in the latest generation, rather than present from the start.
+    pub fn is_new(&self, x: i64, y: i64) -> bool {
+        self.born
+            .get(&(x, y))
+            .is_some_and(|&b| b == self.generation && b > self.start)
+    }
+
+    /// Recently dead cells with the generations since they died, 0 for the latest generation.
+    pub fn trail(&self) -> impl Iterator<Item = ((i64, i64), usize)> + '_ {
+        self.died.iter().map(|(&c, &d)| (c, self.generation - d))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::Universe;
+
+    #[test]
+    fn blinker_history() {
+        let mut universe = Universe::new();
+        for x in -1..=1 {
+            universe.set(GridCoord::Valid(x, 0));
+        }
+        let mut ages = Ages::new(&universe, 3);
+        assert_eq!(ages.age(0, 0), Some(0));
+        assert!(!ages.is_new(0, 0));
+
+        universe.update();
+        ages.observe(&universe);
+        assert_eq!(ages.age(0, 0), Some(1));
+        assert_eq!(ages.age(0, 1), Some(0));
+        assert!(ages.is_new(0, 1) && !ages.is_new(0, 0));
+        let mut trail: Vec<_> = ages.trail().collect();
+        trail.sort();
+        assert_eq!(trail, vec![((-1, 0), 0), ((1, 0), 0)]);
+
+        // The ends are reborn, and the cells which just died join the trail.
+        universe.update();
+        ages.observe(&universe);
+        assert_eq!(ages.age(-1, 0), Some(0));
+        assert_eq!(ages.age(0, 0), Some(2));
+        let mut trail: Vec<_> = ages.trail().collect();
+        trail.sort();
+        assert_eq!(trail, vec![((0, -1), 0), ((0, 1), 0)]);
+
+        // Dead cells are forgotten after the trail length.
+        for c in universe.live_cells() {
+            universe.unset(c);
+        }
+        for _ in 0..3 {
+            universe.update();
+            ages.observe(&universe);
+        }
+        let mut trail: Vec<_> = ages.trail().collect();
+        trail.sort();
+        assert_eq!(trail, vec![((-1, 0), 2), ((0, 0), 2), ((1, 0), 2)]);
+        universe.update();
+        ages.observe(&universe);
+        assert_eq!(ages.trail().count(), 0);
+    }
+}
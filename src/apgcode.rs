@@ -0,0 +1,213 @@
+//! Naming objects by apgcode (see: https://conwaylife.com/wiki/Apgcode).
+
+use crate::grid::{Bounds, GridCoord, Life, Universe};
+use crate::period::{PeriodDetector, Periodicity};
+use crate::rule::Rule;
+
+type Transform = fn(i64, i64) -> (i64, i64);
+
+// The eight rotations and reflections of the plane.
+const ORIENTATIONS: [Transform; 8] = [
+    |x, y| (x, y),
+    |x, y| (-x, y),
+    |x, y| (x, -y),
+    |x, y| (-x, -y),
+    |x, y| (y, x),
+    |x, y| (-y, x),
+    |x, y| (y, -x),
+    |x, y| (-y, -x),
+];
+
+const DIGITS: &[u8; 32] = b"0123456789abcdefghijklmnopqrstuv";
+
+// Encode a run of empty columns.
+fn push_zeros(code: &mut String, mut zeros: usize) {
+    while zeros > 0 {
+        let n = zeros.min(39);
+        match n {
+            1 => code.push('0'),
+            2 => code.push('w'),
+            3 => code.push('x'),
+            _ => {
+                code.push('y');
+                code.push(char::from_digit((n - 4) as u32, 36).expect("Valid digit"));
+            }
+        }
+        zeros -= n;
+    }
+}
+
+/// Extended Wechsler format for the cells, which are assumed to have their top left at 0,0.
+pub fn wechsler(cells: &[(i64, i64)]) -> String {
+    let width = cells.iter().map(|c| c.0 + 1).max().unwrap_or(0) as usize;
+    let height = cells.iter().map(|c| c.1 + 1).max().unwrap_or(0) as usize;
+
+    // Five row strips, each column encoded as a 5 bit value.
+    let strips = height.div_ceil(5);
+    let mut columns = vec![vec![0u8; width]; strips];
+    for &(x, y) in cells {
+        columns[y as usize / 5][x as usize] |= 1 << (y % 5);
+    }
+
+    let mut code = String::new();
+    for (i, strip) in columns.iter().enumerate() {
+        if i > 0 {
+            code.push('z');
+        }
+
+        let mut zeros = 0;
+        for &c in strip {
+            if c == 0 {
+                zeros += 1;
+            } else {
+                push_zeros(&mut code, zeros);
+                zeros = 0;
+                code.push(DIGITS[c as usize] as char);
+            }
+        }
+    }
+
+    code
+}
+
+// Codes are ordered shortest first, then lexicographically.
+fn better(a: &str, b: &str) -> bool {
+    (a.len(), a) < (b.len(), b)
+}
+
+/// The Wechsler code of the cells in the orientation giving the best code.
+pub fn canonical(cells: &[GridCoord]) -> String {
+    let points: Vec<(i64, i64)> = cells
+        .iter()
+        .filter_map(|c| match *c {
+            GridCoord::Valid(x, y) => Some((x, y)),
+            GridCoord::OutOfBounds => None,
+        })
+        .collect();
+
+    let mut best: Option<String> = None;
+    for orient in ORIENTATIONS {
+        let oriented: Vec<GridCoord> = points
+            .iter()
+            .map(|&(x, y)| {
+                let (x, y) = orient(x, y);
+                GridCoord::Valid(x, y)
+            })
+            .collect();
+        let Some(bounds) = Bounds::of(&oriented) else {
+            return String::from("0");
+        };
+
+        let normalised: Vec<(i64, i64)> = oriented
+            .iter()
+            .filter_map(|c| match *c {
+                GridCoord::Valid(x, y) => Some((x - bounds.min_x, y - bounds.min_y)),
+                GridCoord::OutOfBounds => None,
+            })
+            .collect();
+        let code = wechsler(&normalised);
+        if best.as_ref().is_none_or(|b| better(&code, b)) {
+            best = Some(code);
+        }
+    }
+
+    best.unwrap_or_default()
+}
+
+/// Run an isolated object until it repeats and name it: xs<population> for still lifes,
+/// xp<period> for oscillators and xq<period> for spaceships, followed by the best code over
+/// all phases. Objects which do not repeat within max_period are named zz_UNKNOWN.
+pub fn apgcode(cells: &[GridCoord], rule: Rule, max_period: usize) -> String {
+    let mut universe = Universe::with_rule(rule);
+    for &c in cells {
+        universe.grid.set(c);
+    }
+
+    let mut detector = PeriodDetector::with_max_period(max_period);
+    let mut phases: Vec<String> = vec![canonical(cells)];
+    detector.observe(&universe);
+
+    for _ in 0..=max_period {
+        universe.update();
+        let live = universe.live_cells();
+        let periodicity = detector.observe(&universe);
+        phases.push(canonical(&live));
+
+        let (prefix, period) = match periodicity {
+            None => continue,
+            Some(Periodicity::Empty) => return "xs0_0".to_owned(),
+            Some(Periodicity::StillLife) => (format!("xs{}", live.len()), 1),
+            Some(Periodicity::Oscillator { period }) => (format!("xp{period}"), period),
+            Some(Periodicity::Spaceship { period, .. }) => (format!("xq{period}"), period),
+        };
+
+        let best = phases[phases.len() - period..]
+            .iter()
+            .fold(None::<&String>, |best, code| match best {
+                Some(b) if !better(code, b) => Some(b),
+                _ => Some(code),
+            })
+            .expect("At least one phase");
+
+        return format!("{prefix}_{best}");
+    }
+
+    "zz_UNKNOWN".to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cells(rle_rows: &[&str]) -> Vec<GridCoord> {
+        let mut cells = vec![];
+        for (y, row) in rle_rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                if c == 'o' {
+                    cells.push(GridCoord::Valid(x as i64 + 10, y as i64 - 7));
+                }
+            }
+        }
+        cells
+    }
+
+    fn code(rows: &[&str]) -> String {
+        apgcode(&cells(rows), Rule::default(), 100)
+    }
+
+    #[test]
+    fn zero_runs() {
+        let mut code = String::new();
+        push_zeros(&mut code, 1);
+        push_zeros(&mut code, 2);
+        push_zeros(&mut code, 3);
+        push_zeros(&mut code, 4);
+        push_zeros(&mut code, 39);
+        push_zeros(&mut code, 40);
+        assert_eq!(code, "0wxy0yzyz0");
+    }
+
+    #[test]
+    fn common_objects() {
+        assert_eq!(code(&["oo", "oo"]), "xs4_33");
+        assert_eq!(code(&["ooo"]), "xp2_7");
+        assert_eq!(code(&[".oo.", "o..o", ".oo."]), "xs6_696");
+        assert_eq!(code(&[".o.", "..o", "ooo"]), "xq4_153");
+        assert_eq!(code(&[".oo.", "o..o", ".o.o", "..o."]), "xs7_2596");
+        assert_eq!(code(&["oo.", "o.o", ".o."]), "xs5_253");
+        assert_eq!(code(&[".o.", "o.o", ".o."]), "xs4_252");
+    }
+
+    #[test]
+    fn multiple_strips() {
+        // Vertical line of 7 spans two strips.
+        let line: Vec<(i64, i64)> = (0..7).map(|y| (0, y)).collect();
+        assert_eq!(wechsler(&line), "vz3");
+    }
+
+    #[test]
+    fn unknown() {
+        // R-pentomino takes over 1000 generations to stabilise.
+        assert_eq!(code(&[".oo", "oo.", ".o."]), "zz_UNKNOWN");
+    }
+}
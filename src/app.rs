@@ -0,0 +1,1634 @@
+#[allow(unused_imports)]
+use ggez::glam::*;
+use ggez::graphics::{Image, ImageFormat, MeshBuilder, PxScale, Sampler};
+use ggez::input::keyboard::{KeyCode, KeyInput, KeyMods};
+use ggez::input::mouse::MouseButton;
+use ggez::{
+    conf::WindowMode,
+    event::{self, ControlFlow, EventHandler, EventLoop},
+    graphics::{self, Color, DrawMode, DrawParam, Mesh, Rect, Text},
+    mint::Point2,
+    Context, ContextBuilder, GameError, GameResult,
+};
+use log::*;
+use std::time::{Duration, Instant, SystemTime};
+use winit::event::{ElementState, Event, KeyboardInput, MouseScrollDelta, WindowEvent};
+
+use boundlesse::ages::Ages;
+use boundlesse::grid::{Bounds, GridCoord, Life};
+use boundlesse::objects::{analyse, Object, Tracker};
+use boundlesse::period::PeriodDetector;
+use boundlesse::record::{Recorder, Recording};
+use boundlesse::render::render;
+use boundlesse::rle::{load_rle, save_rle_states, Injector};
+use boundlesse::rule::Rule;
+use boundlesse::soup::{next_seed, Fill};
+use boundlesse::stats::History;
+
+use crate::camera::{Camera, MAJOR_LINE_EVERY};
+use crate::cli::Options;
+use crate::keymap::{Action, Keymap};
+use crate::minimap::Layout;
+use crate::plot::{Plot, PlotColors, MAX_SPAN};
+use crate::scheduler::Scheduler;
+use crate::settings::{
+    parse_color, CellColors, Layers, RecordFormat, SaveTarget, Settings, SubPixel, Theme,
+};
+
+fn now() -> u128 {
+    let duration_since_epoch = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap();
+    duration_since_epoch.as_nanos() / 1000
+}
+
+// Statistics are exported to this file in the current directory.
+const STATS_FILE: &str = "boundlesse-stats.csv";
+const PATTERN_FILE: &str = "boundlesse-pattern.rle";
+const IMAGE_FILE: &str = "boundlesse-image.png";
+// Recordings are saved as this with a .gif extension, or numbered from it with .png.
+const RECORDING_FILE: &str = "boundlesse-recording";
+
+// Mouse wheel zoom factor per notch.
+const WHEEL_ZOOM: f64 = 1.25;
+
+// A drag only flings the view if the pointer was still moving this recently.
+const FLING_WINDOW: Duration = Duration::from_millis(50);
+
+// What a pointer drag does.
+enum DragMode {
+    Pan,
+    // Selecting from this grid position.
+    Select((f64, f64)),
+    // Moving the view around the minimap, as laid out when the drag started.
+    Navigate(Layout),
+}
+
+struct Drag {
+    mode: DragMode,
+    last: Instant,
+    // Smoothed pointer velocity in pixels per second.
+    velocity: (f64, f64),
+}
+
+// The cells spanned by two grid positions.
+fn cell_bounds(a: (f64, f64), b: (f64, f64)) -> Bounds {
+    Bounds {
+        min_x: a.0.min(b.0).floor() as i64,
+        min_y: a.1.min(b.1).floor() as i64,
+        max_x: a.0.max(b.0).floor() as i64,
+        max_y: a.1.max(b.1).floor() as i64,
+    }
+}
+
+// Screen rectangle around the cells in the bounds.
+fn bounds_rect(camera: &Camera, b: &Bounds, margin: f32) -> Rect {
+    let top_left = camera.grid_to_screen(b.min_x as f64, b.min_y as f64);
+    let bottom_right = camera.grid_to_screen((b.max_x + 1) as f64, (b.max_y + 1) as f64);
+    Rect::new(
+        top_left.x - margin,
+        top_left.y - margin,
+        bottom_right.x - top_left.x + 2.0 * margin,
+        bottom_right.y - top_left.y + 2.0 * margin,
+    )
+}
+
+struct Palette {
+    background: Color,
+    cell: Color,
+    line: Color,
+    text: Color,
+    object: Color,
+    age: Vec<Color>,
+    born: Color,
+    died: Color,
+    trail: Color,
+    dying: Vec<Color>,
+}
+
+impl Palette {
+    fn new(theme: &Theme) -> anyhow::Result<Self> {
+        let color = |s: &str| -> anyhow::Result<Color> {
+            let [r, g, b, a] = parse_color(s)?;
+            Ok(Color::from_rgba(r, g, b, a))
+        };
+
+        Ok(Palette {
+            background: color(&theme.background)?,
+            cell: color(&theme.cell)?,
+            line: color(&theme.line)?,
+            text: color(&theme.text)?,
+            object: color(&theme.object)?,
+            age: theme
+                .age
+                .iter()
+                .map(|c| color(c))
+                .collect::<anyhow::Result<_>>()?,
+            born: color(&theme.born)?,
+            died: color(&theme.died)?,
+            trail: color(&theme.trail)?,
+            dying: theme
+                .dying
+                .iter()
+                .map(|c| color(c))
+                .collect::<anyhow::Result<_>>()?,
+        })
+    }
+
+    // The age colours spread evenly from just born to max_age generations.
+    fn age(&self, age: usize, max_age: u32) -> Color {
+        let t = (age as f32 / max_age as f32).min(1.0) * (self.age.len() - 1) as f32;
+        let i = (t as usize).min(self.age.len().saturating_sub(2));
+        match self.age.get(i + 1) {
+            Some(&next) => blend(self.age[i], next, t - i as f32),
+            None => self.age[i],
+        }
+    }
+
+    // The dying colours spread evenly over the dying states of a rule with `states` states.
+    fn dying(&self, state: u8, states: u8) -> Color {
+        let t = if states > 3 {
+            (state - 2) as f32 / (states - 3) as f32 * (self.dying.len() - 1) as f32
+        } else {
+            0.0
+        };
+        let i = (t as usize).min(self.dying.len().saturating_sub(2));
+        match self.dying.get(i + 1) {
+            Some(&next) => blend(self.dying[i], next, (t - i as f32).min(1.0)),
+            None => self.dying[i],
+        }
+    }
+}
+
+fn blend(a: Color, b: Color, t: f32) -> Color {
+    Color::new(
+        a.r + (b.r - a.r) * t,
+        a.g + (b.g - a.g) * t,
+        a.b + (b.b - a.b) * t,
+        a.a + (b.a - a.a) * t,
+    )
+}
+
+struct State {
+    camera: Camera,
+    drag: Option<Drag>,
+    selection: Option<Bounds>,
+    // Object kept centred as it moves.
+    following: Option<Tracker>,
+    universe: Box<dyn Life>,
+    period: PeriodDetector,
+    settings: Settings,
+    // The settings files, and the settings as they were when the app started.
+    layers: Layers,
+    start_settings: Settings,
+    // Waiting for the file to save the settings to.
+    save_prompt: bool,
+    keymap: Keymap,
+    theme: String,
+    palette: Palette,
+    // Random soups are filled from the seed, which moves on once it has been used.
+    fill: Fill,
+    seed: String,
+    seed_used: bool,
+    // Seed being typed, if any.
+    seed_entry: Option<String>,
+    show_grid: bool,
+    show_rulers: bool,
+    running: bool,
+    show_header: bool,
+    show_help: bool,
+    show_objects: bool,
+    show_minimap: bool,
+    cell_colors: CellColors,
+    // Cell histories for colouring, kept while the colours need them.
+    ages: Option<Ages>,
+    recorder: Option<Recorder>,
+    history: History,
+    plot: Plot,
+    // Objects in the current generation, found when first shown.
+    objects: Option<Vec<Object>>,
+    notice: Option<String>,
+    dirty: bool,
+    scheduler: Scheduler,
+    updates: u64,
+    draws: u32,
+}
+
+impl State {
+    pub fn new(
+        _ctx: &mut Context,
+        options: &Options,
+        settings: Settings,
+        layers: Layers,
+    ) -> anyhow::Result<Self> {
+        let mut state = State {
+            camera: Camera::new(options.window_size, options.cell_size),
+            drag: None,
+            selection: None,
+            following: None,
+            universe: settings.backend.create(settings.rule),
+            period: PeriodDetector::new(),
+            keymap: Keymap::new(&settings.keys)?,
+            theme: settings.theme.clone(),
+            palette: Palette::new(&settings.theme(&settings.theme)?)?,
+            fill: Fill::new(options.pattern_size, options.density, options.symmetry)?,
+            seed: options.seed.clone(),
+            seed_used: false,
+            seed_entry: None,
+            show_grid: settings.show_grid,
+            show_rulers: settings.show_rulers,
+            running: false,
+            show_header: settings.show_header,
+            show_help: false,
+            show_objects: settings.show_objects,
+            show_minimap: settings.show_minimap,
+            cell_colors: settings.cell_colors,
+            ages: None,
+            recorder: None,
+            history: History::new(MAX_SPAN),
+            plot: Plot::new(),
+            objects: None,
+            notice: None,
+            dirty: true,
+            scheduler: Scheduler::new(
+                settings.speed,
+                settings.gps,
+                settings.gens_per_frame,
+                settings.max_fps,
+                settings.step_exponent,
+            ),
+            updates: 0,
+            draws: 0,
+            start_settings: settings.clone(),
+            settings,
+            layers,
+            save_prompt: false,
+        };
+        state.history.reset(state.universe.as_ref());
+
+        Ok(state)
+    }
+
+    // Save the settings changed in the app to the per-user or per-project settings file.
+    pub fn save_settings(&mut self, target: SaveTarget) {
+        self.settings.speed = self.scheduler.speed;
+        self.settings.gps = self.scheduler.gps;
+        self.settings.gens_per_frame = self.scheduler.gens_per_frame;
+        self.settings.step_exponent = self.scheduler.step_exponent;
+        self.settings.rule = self.universe.rule();
+        self.settings.show_grid = self.show_grid;
+        self.settings.show_rulers = self.show_rulers;
+        self.settings.show_header = self.show_header;
+        self.settings.show_objects = self.show_objects;
+        self.settings.show_minimap = self.show_minimap;
+        self.settings.cell_colors = self.cell_colors;
+        self.settings.theme = self.theme.clone();
+
+        let saved = self
+            .layers
+            .save(target, &self.start_settings, &self.settings);
+        self.notice = Some(match saved {
+            Ok(path) => format!("Settings saved to {}", path.display()),
+            Err(e) => {
+                error!("Failed to save settings: {:?}", e);
+                format!("Failed to save settings: {}", e)
+            }
+        });
+    }
+
+    // Replace the universe with the soup from the current seed, centred in the view.
+    pub fn seed_rand(&mut self) {
+        let (w, h) = self.fill.size();
+        let off_x = self.camera.centre.0.round() as i64 - w / 2;
+        let off_y = self.camera.centre.1.round() as i64 - h / 2;
+
+        self.universe = self.settings.backend.create(self.universe.rule());
+        for c in self.fill.cells(&self.seed) {
+            self.universe.set(c.adjust(off_x, off_y));
+        }
+        info!("Filled soup from seed: {}", self.seed);
+
+        self.seed_used = true;
+        self.edited();
+    }
+
+    // Forget anything derived from earlier generations, as the universe has been changed.
+    fn edited(&mut self) {
+        self.period.reset();
+        self.objects = None;
+        self.following = None;
+        self.ages = None;
+        self.history.reset(self.universe.as_ref());
+    }
+
+    pub fn next_seed(&mut self) {
+        if self.seed_used {
+            self.seed = next_seed(&self.seed);
+        }
+        self.seed_rand();
+    }
+
+    // Keys while choosing where to save the settings: u for the per-user file, p for the
+    // per-project one, anything else cancels.
+    fn save_prompt_key(&mut self, keycode: KeyCode) {
+        self.save_prompt = false;
+        match keycode {
+            KeyCode::U if self.layers.path(SaveTarget::User).is_some() => {
+                self.save_settings(SaveTarget::User)
+            }
+            KeyCode::P => self.save_settings(SaveTarget::Project),
+            _ => {}
+        }
+    }
+
+    fn save_prompt_text(&self) -> String {
+        let choices: Vec<String> = [("u", SaveTarget::User), ("p", SaveTarget::Project)]
+            .into_iter()
+            .filter_map(|(key, target)| {
+                let path = self.layers.path(target)?;
+                Some(format!("{key} = {}", path.display()))
+            })
+            .collect();
+        format!(
+            "Save settings to: {} (Escape to cancel)",
+            choices.join(", ")
+        )
+    }
+
+    // Keys while a seed is being typed: Return fills from it, Escape abandons it.
+    fn seed_entry_key(&mut self, keycode: KeyCode) {
+        let Some(entry) = &mut self.seed_entry else {
+            return;
+        };
+
+        match keycode {
+            KeyCode::Return => {
+                if !entry.is_empty() {
+                    self.seed = entry.clone();
+                    self.seed_rand();
+                }
+                self.seed_entry = None;
+            }
+            KeyCode::Escape => self.seed_entry = None,
+            KeyCode::Back => {
+                entry.pop();
+            }
+            _ => {}
+        }
+    }
+
+    // Advance a step of 2^k generations.
+    pub fn step(&mut self) {
+        // The plot, ages, recording, followed object and period detector each need to see every
+        // generation, so a step of 2^k generations is run one generation at a time.
+        for _ in 0..self.scheduler.step() {
+            self.next_generation();
+        }
+        self.objects = None;
+    }
+
+    fn next_generation(&mut self) {
+        let start = Instant::now();
+        self.universe.update();
+        let update = start.elapsed();
+
+        self.period.observe(self.universe.as_ref());
+        self.history.record(self.universe.as_ref(), update);
+        if let Some(ages) = &mut self.ages {
+            ages.observe(self.universe.as_ref());
+        }
+        if let Some(recorder) = &mut self.recorder {
+            if recorder.observe(self.universe.as_ref()) {
+                self.finish_recording();
+            }
+        }
+
+        if let Some(tracker) = &mut self.following {
+            if tracker.track(self.universe.as_ref(), 1) {
+                self.camera.centre = tracker.centre();
+            } else {
+                self.following = None;
+                self.notice = Some("Lost the followed object".to_owned());
+            }
+        }
+    }
+
+    fn objects(&mut self) -> &Vec<Object> {
+        self.objects.get_or_insert_with(|| {
+            analyse(
+                &self.universe.live_cells(),
+                self.universe.rule(),
+                &self.settings.separation,
+            )
+        })
+    }
+
+    fn fit_pattern(&mut self) {
+        self.following = None;
+        if let Some(bounds) = self.universe.bounds() {
+            self.camera.fit(&bounds);
+        }
+    }
+
+    fn fit_selection(&mut self) {
+        match self.selection {
+            Some(selection) => {
+                self.following = None;
+                self.camera.fit(&selection);
+            }
+            None => self.notice = Some("Nothing selected".to_owned()),
+        }
+    }
+
+    // Follow the selected cells, otherwise the object nearest the pointer, or stop following.
+    fn follow(&mut self, pointer: Vec2) {
+        if self.following.take().is_some() {
+            self.notice = Some("Stopped following".to_owned());
+            return;
+        }
+
+        let target = match self.selection {
+            Some(selection) => Bounds::of(&self.universe.cells_in(&selection))
+                .map(|b| (b, "the selection".to_owned())),
+            None => {
+                let (px, py) = self.camera.screen_to_grid(pointer);
+                let distance = |b: &Bounds| {
+                    let dx = (b.min_x as f64 - px)
+                        .max(px - (b.max_x + 1) as f64)
+                        .max(0.0);
+                    let dy = (b.min_y as f64 - py)
+                        .max(py - (b.max_y + 1) as f64)
+                        .max(0.0);
+                    dx.hypot(dy)
+                };
+                self.objects()
+                    .iter()
+                    .min_by(|a, b| distance(&a.bounds).total_cmp(&distance(&b.bounds)))
+                    .map(|o| (o.bounds, o.label()))
+            }
+        };
+
+        match target {
+            Some((bounds, label)) => {
+                let tracker = Tracker::new(bounds);
+                self.camera.stop();
+                self.camera.centre = tracker.centre();
+                self.following = Some(tracker);
+                self.notice = Some(format!("Following {label}"));
+            }
+            None => self.notice = Some("Nothing to follow".to_owned()),
+        }
+    }
+
+    fn population(&self) -> usize {
+        self.history.latest().map_or(0, |s| s.population)
+    }
+
+    fn export_stats(&mut self) {
+        self.notice = Some(match self.history.save_csv(STATS_FILE) {
+            Ok(()) => format!("Statistics exported to {}", STATS_FILE),
+            Err(e) => {
+                error!("Failed to export statistics: {:?}", e);
+                format!("Failed to export statistics: {}", e)
+            }
+        });
+    }
+
+    // Switch to the next theme, built-in themes first.
+    fn next_theme(&mut self) {
+        let names = self.settings.theme_names();
+        let next = names
+            .iter()
+            .position(|n| *n == self.theme)
+            .map_or(0, |i| (i + 1) % names.len());
+        let palette = self
+            .settings
+            .theme(&names[next])
+            .and_then(|theme| Palette::new(&theme));
+
+        self.notice = Some(match palette {
+            Ok(palette) => {
+                self.palette = palette;
+                self.theme = names[next].clone();
+                format!("Theme: {}", self.theme)
+            }
+            Err(e) => {
+                error!("Failed to switch theme: {:?}", e);
+                format!("Failed to switch theme: {}", e)
+            }
+        });
+    }
+
+    // Save the selected cells, or all of them if there is no selection.
+    fn save_pattern(&mut self) {
+        let cells = match &self.selection {
+            Some(selection) => self.universe.states_in(selection),
+            None => self.universe.cell_states(),
+        };
+        self.notice = Some(
+            match save_rle_states(PATTERN_FILE, &cells, &self.universe.rule()) {
+                Ok(()) => format!("Saved {} cells to {}", cells.len(), PATTERN_FILE),
+                Err(e) => {
+                    error!("Failed to save pattern: {:?}", e);
+                    format!("Failed to save pattern: {}", e)
+                }
+            },
+        );
+    }
+
+    // Draw the cells in view, coloured as chosen, returning how many were drawn.
+    fn draw_cells(
+        &mut self,
+        ctx: &mut Context,
+        canvas: &mut graphics::Canvas,
+    ) -> GameResult<usize> {
+        let camera = &self.camera;
+        let visible = camera.visible();
+        let cs = camera.cell_size as f32;
+        let rect = |x: i64, y: i64| {
+            let p = camera.grid_to_screen(x as f64, y as f64);
+            Rect::new(p.x, p.y, cs, cs)
+        };
+
+        let ages = match self.cell_colors {
+            CellColors::Plain => None,
+            _ => {
+                let universe = self.universe.as_ref();
+                let trail = self.settings.trail_length as usize;
+                Some(&*self.ages.get_or_insert_with(|| Ages::new(universe, trail)))
+            }
+        };
+
+        let mut cb = MeshBuilder::new();
+
+        // Dead cells first: those which just died, or the fading trail.
+        if let Some(ages) = ages {
+            for ((x, y), since) in ages.trail() {
+                if !visible.contains(x, y) {
+                    continue;
+                }
+                let color = match self.cell_colors {
+                    CellColors::Changes if since == 0 => self.palette.died,
+                    CellColors::Heat => {
+                        let fade = 1.0 - since as f32 / self.settings.trail_length as f32;
+                        let mut trail = self.palette.trail;
+                        trail.a *= fade;
+                        trail
+                    }
+                    _ => continue,
+                };
+                cb.rectangle(DrawMode::fill(), rect(x, y), color)?;
+            }
+        }
+
+        // Then the dying cells of Generations rules.
+        let mut cells_drawn = 0;
+        let states = self.universe.rule().states();
+        for (gc, state) in self.universe.dying_in(&visible) {
+            if let GridCoord::Valid(x, y) = gc {
+                cb.rectangle(
+                    DrawMode::fill(),
+                    rect(x, y),
+                    self.palette.dying(state, states),
+                )?;
+                cells_drawn += 1;
+            }
+        }
+
+        for gc in self.universe.cells_in(&visible) {
+            if let GridCoord::Valid(x, y) = gc {
+                let color = match (self.cell_colors, ages) {
+                    (CellColors::Age, Some(ages)) => self
+                        .palette
+                        .age(ages.age(x, y).unwrap_or(0), self.settings.max_age),
+                    (CellColors::Changes, Some(ages)) if ages.is_new(x, y) => self.palette.born,
+                    _ => self.palette.cell,
+                };
+                cb.rectangle(DrawMode::fill(), rect(x, y), color)?;
+                cells_drawn += 1;
+            }
+        }
+
+        canvas.draw(&Mesh::from_data(ctx, cb.build()), DrawParam::default());
+
+        Ok(cells_drawn)
+    }
+
+    // Rulers along the top (below the header) and left edges, labelling the major grid lines
+    // with their coordinates and marking the pointer's cell.
+    fn draw_rulers(
+        &self,
+        ctx: &mut Context,
+        canvas: &mut graphics::Canvas,
+        pointer: (i64, i64),
+    ) -> GameResult {
+        let camera = &self.camera;
+        let visible = camera.visible();
+        let (window_w, window_h) = camera.window_size;
+        let font_size = self.settings.header_font_size * 0.4;
+        let top = if self.show_header {
+            2.0 + 2.0 * self.settings.header_font_size
+        } else {
+            0.0
+        };
+        let across = font_size + 8.0;
+        let side = font_size * 4.0;
+        let top_ruler = Rect::new(0.0, top, window_w, across);
+        let left_ruler = Rect::new(0.0, top + across, side, window_h - top - across);
+
+        let mut background = self.palette.background;
+        background.a = 0.9;
+        let mut mb = MeshBuilder::new();
+        mb.rectangle(DrawMode::fill(), top_ruler, background)?;
+        mb.rectangle(DrawMode::fill(), left_ruler, background)?;
+        mb.line(
+            &[vec2(side, top + across), vec2(window_w, top + across)],
+            1.0,
+            self.palette.line,
+        )?;
+        mb.line(
+            &[vec2(side, top + across), vec2(side, window_h)],
+            1.0,
+            self.palette.line,
+        )?;
+
+        // Ticks at every grid line, longer at major lines, which are labelled if there's room.
+        let spacing = camera.line_spacing();
+        let major = spacing * MAJOR_LINE_EVERY;
+        let labelled = if major as f64 * camera.cell_size < 80.0 {
+            major * 5
+        } else {
+            major
+        };
+        let scale = PxScale::from(font_size);
+        let mut labels = vec![];
+        for x in Camera::lines(visible.min_x, visible.max_x, spacing) {
+            let sx = camera.grid_to_screen(x as f64, 0.0).x;
+            if sx < side {
+                continue;
+            }
+            let tick = if x % major == 0 {
+                across / 2.0
+            } else {
+                across / 4.0
+            };
+            mb.line(
+                &[vec2(sx, top + across - tick), vec2(sx, top + across)],
+                1.0,
+                self.palette.line,
+            )?;
+            if x % labelled == 0 {
+                labels.push((x, vec2(sx + 2.0, top + 2.0)));
+            }
+        }
+        for y in Camera::lines(visible.min_y, visible.max_y, spacing) {
+            let sy = camera.grid_to_screen(0.0, y as f64).y;
+            if sy < top + across {
+                continue;
+            }
+            let tick = if y % major == 0 {
+                side / 4.0
+            } else {
+                side / 8.0
+            };
+            mb.line(
+                &[vec2(side - tick, sy), vec2(side, sy)],
+                1.0,
+                self.palette.line,
+            )?;
+            if y % labelled == 0 {
+                labels.push((y, vec2(2.0, sy + 2.0)));
+            }
+        }
+
+        // The pointer's cell.
+        let p = camera.grid_to_screen(pointer.0 as f64, pointer.1 as f64);
+        let cs = (camera.cell_size as f32).max(1.0);
+        if p.x >= side {
+            mb.rectangle(
+                DrawMode::fill(),
+                Rect::new(p.x, top + across - 4.0, cs, 4.0),
+                self.palette.object,
+            )?;
+        }
+        if p.y >= top + across {
+            mb.rectangle(
+                DrawMode::fill(),
+                Rect::new(side - 4.0, p.y, 4.0, cs),
+                self.palette.object,
+            )?;
+        }
+
+        canvas.draw(&Mesh::from_data(ctx, mb.build()), DrawParam::default());
+        for (c, at) in labels {
+            let mut text = Text::new(c.to_string());
+            text.set_scale(scale);
+            canvas.draw(&text, DrawParam::from(at).color(self.palette.text));
+        }
+
+        Ok(())
+    }
+
+    // Draw the cells in the bounds to a PNG file, at the image cell size rather than the view's.
+    fn export_image(&mut self, bounds: Option<Bounds>, what: &str) {
+        let Some(bounds) = bounds else {
+            self.notice = Some("Nothing to export".to_owned());
+            return;
+        };
+
+        let saved = self.settings.image_style(&self.theme).and_then(|style| {
+            let image = render(self.universe.as_ref(), &bounds, &style)?;
+            image.save_png(IMAGE_FILE)?;
+            Ok(image)
+        });
+        self.notice = Some(match saved {
+            Ok(image) => format!(
+                "Exported {what} as {}x{} to {}",
+                image.width, image.height, IMAGE_FILE
+            ),
+            Err(e) => {
+                error!("Failed to export image: {:?}", e);
+                format!("Failed to export image: {}", e)
+            }
+        });
+    }
+
+    // Record the selection, or the view if nothing is selected, from the current generation.
+    fn start_recording(&mut self) {
+        let (region, what) = match self.selection {
+            Some(selection) => (selection, "the selection"),
+            None => (self.camera.visible(), "the view"),
+        };
+        let path = match self.settings.record_format {
+            RecordFormat::Gif => format!("{RECORDING_FILE}.gif"),
+            RecordFormat::Png => format!("{RECORDING_FILE}.png"),
+        };
+
+        let recorder = self.settings.image_style(&self.theme).and_then(|style| {
+            Recorder::new(Recording {
+                path,
+                region: Some(region),
+                frames: self.settings.record_frames,
+                step: self.settings.record_step,
+                style,
+                delay: Duration::from_millis(self.settings.record_delay),
+            })
+        });
+        match recorder {
+            Ok(mut recorder) => {
+                recorder.observe(self.universe.as_ref());
+                self.recorder = Some(recorder);
+                self.notice = Some(format!("Recording {what}"));
+            }
+            Err(e) => {
+                error!("Failed to start recording: {:?}", e);
+                self.notice = Some(format!("Failed to start recording: {}", e));
+            }
+        }
+    }
+
+    // Write out the frames recorded so far.
+    fn finish_recording(&mut self) {
+        let Some(recorder) = self.recorder.take() else {
+            return;
+        };
+
+        let path = recorder.path().to_owned();
+        self.notice = Some(match recorder.finish() {
+            Ok(frames) => format!("Recorded {frames} frames to {path}"),
+            Err(e) => {
+                error!("Failed to save recording: {:?}", e);
+                format!("Failed to save recording: {}", e)
+            }
+        });
+    }
+
+    // Outline and label each object in view.
+    fn draw_objects(&mut self, ctx: &mut Context, canvas: &mut graphics::Canvas) -> GameResult {
+        let objects = self.objects.get_or_insert_with(|| {
+            analyse(
+                &self.universe.live_cells(),
+                self.universe.rule(),
+                &self.settings.separation,
+            )
+        });
+
+        let camera = &self.camera;
+        let visible = camera.visible();
+        let scale = PxScale::from(self.settings.header_font_size * 0.5);
+        let mut mb = MeshBuilder::new();
+        let mut outlines = 0;
+        for object in objects.iter() {
+            let b = &object.bounds;
+            if b.max_x < visible.min_x
+                || b.max_y < visible.min_y
+                || b.min_x > visible.max_x
+                || b.min_y > visible.max_y
+            {
+                continue;
+            }
+
+            let rect = bounds_rect(camera, b, 2.0);
+            mb.rectangle(DrawMode::stroke(2.0), rect, self.palette.object)?;
+            outlines += 1;
+
+            let mut label = Text::new(object.label());
+            label.set_scale(scale);
+            canvas.draw(
+                &label,
+                DrawParam::from(vec2(rect.x, rect.y - scale.y)).color(self.palette.object),
+            );
+        }
+
+        if outlines > 0 {
+            canvas.draw(&Mesh::from_data(ctx, mb.build()), DrawParam::default());
+        }
+
+        Ok(())
+    }
+
+    // Outline the selection and the followed object.
+    fn draw_selection(&self, ctx: &mut Context, canvas: &mut graphics::Canvas) -> GameResult {
+        let mut mb = MeshBuilder::new();
+        if let Some(selection) = &self.selection {
+            let rect = bounds_rect(&self.camera, selection, 0.0);
+            mb.rectangle(DrawMode::stroke(1.5), rect, self.palette.text)?;
+        }
+        if let Some(tracker) = &self.following {
+            let rect = bounds_rect(&self.camera, &tracker.bounds(), 4.0);
+            mb.rectangle(DrawMode::stroke(2.0), rect, self.palette.object)?;
+        }
+
+        if self.selection.is_some() || self.following.is_some() {
+            canvas.draw(&Mesh::from_data(ctx, mb.build()), DrawParam::default());
+        }
+
+        Ok(())
+    }
+
+    // Zoomed out, draw each pixel from the count of the live cells it covers.
+    fn draw_density(
+        &mut self,
+        ctx: &mut Context,
+        canvas: &mut graphics::Canvas,
+        shift: u32,
+    ) -> GameResult {
+        // Blocks of cells are drawn a pixel or a little more across, from the block holding the
+        // top left cell.
+        let camera = &self.camera;
+        let visible = camera.visible();
+        let origin = (visible.min_x >> shift, visible.min_y >> shift);
+        let block = camera.cell_size * (1u64 << shift) as f64;
+        let w = (camera.window_size.0 as f64 / block).ceil() as usize + 1;
+        let h = (camera.window_size.1 as f64 / block).ceil() as usize + 1;
+        if camera.window_size.0 < 1.0 || camera.window_size.1 < 1.0 {
+            return Ok(());
+        }
+
+        let at = camera.grid_to_screen((origin.0 << shift) as f64, (origin.1 << shift) as f64);
+        self.draw_blocks(ctx, canvas, shift, origin, (w, h), (at, block as f32))
+    }
+
+    // Draw the blocks of 2^shift cells from block `origin` at `at`, each `block` pixels across,
+    // shaded by the live cells in each.
+    fn draw_blocks(
+        &mut self,
+        ctx: &mut Context,
+        canvas: &mut graphics::Canvas,
+        shift: u32,
+        origin: (i64, i64),
+        (w, h): (usize, usize),
+        (at, block): (Vec2, f32),
+    ) -> GameResult {
+        let counts = self.universe.block_counts(shift, origin, (w, h));
+
+        let full = (1u64 << (2 * shift)) as f32;
+        let (r, g, b, a) = self.palette.cell.to_rgba();
+        let mut pixels = vec![0u8; w * h * 4];
+        for (&count, pixel) in counts.iter().zip(pixels.chunks_exact_mut(4)) {
+            if count > 0 {
+                // Sparse pixels stay visible when shaded by density.
+                let alpha = match self.settings.sub_pixel {
+                    SubPixel::Density => 0.3 + 0.7 * (count as f32 / full).sqrt(),
+                    SubPixel::AnyAlive => 1.0,
+                };
+                pixel.copy_from_slice(&[r, g, b, (alpha * a as f32) as u8]);
+            }
+        }
+
+        let image = Image::from_pixels(
+            ctx,
+            &pixels,
+            ImageFormat::Rgba8UnormSrgb,
+            w as u32,
+            h as u32,
+        );
+        canvas.set_sampler(Sampler::nearest_clamp());
+        canvas.draw(&image, DrawParam::from(at).scale(vec2(block, block)));
+        canvas.set_default_sampler();
+
+        Ok(())
+    }
+
+    fn minimap_layout(&self) -> Layout {
+        Layout::new(
+            self.universe.bounds(),
+            self.camera.visible(),
+            self.camera.window_size,
+        )
+    }
+
+    // Overview of the populated extent, with the viewport outlined.
+    fn draw_minimap(&mut self, ctx: &mut Context, canvas: &mut graphics::Canvas) -> GameResult {
+        let layout = self.minimap_layout();
+        if canvas.set_scissor_rect(layout.rect).is_err() {
+            // The window is too small.
+            return Ok(());
+        }
+
+        let mut background = self.palette.background;
+        background.a = 0.9;
+        let panel = Mesh::new_rectangle(ctx, DrawMode::fill(), layout.rect, background)?;
+        canvas.draw(&panel, DrawParam::default());
+
+        // As in the main view, blocks of cells are drawn a pixel or a little more across.
+        let shift = (layout.scale.recip().log2() - 1e-9).ceil().max(0.0) as u32;
+        let block = layout.scale * (1u64 << shift) as f64;
+        let extent = layout.extent();
+        let origin = (extent.min_x >> shift, extent.min_y >> shift);
+        let w = (layout.rect.w as f64 / block).ceil() as usize + 1;
+        let h = (layout.rect.h as f64 / block).ceil() as usize + 1;
+        let at = layout.grid_to_screen((origin.0 << shift) as f64, (origin.1 << shift) as f64);
+        self.draw_blocks(ctx, canvas, shift, origin, (w, h), (at, block as f32))?;
+        canvas.set_default_scissor_rect();
+
+        let visible = self.camera.visible();
+        let top_left = layout.grid_to_screen(visible.min_x as f64, visible.min_y as f64);
+        let bottom_right =
+            layout.grid_to_screen((visible.max_x + 1) as f64, (visible.max_y + 1) as f64);
+        let mut mb = MeshBuilder::new();
+        mb.rectangle(
+            DrawMode::stroke(1.5),
+            Rect::new(
+                top_left.x,
+                top_left.y,
+                bottom_right.x - top_left.x,
+                bottom_right.y - top_left.y,
+            ),
+            self.palette.object,
+        )?;
+        mb.rectangle(DrawMode::stroke(2.0), layout.rect, self.palette.line)?;
+        canvas.draw(&Mesh::from_data(ctx, mb.build()), DrawParam::default());
+
+        Ok(())
+    }
+
+    // Overlay listing the current key bindings.
+    fn draw_help(&self, ctx: &mut Context, canvas: &mut graphics::Canvas) -> GameResult {
+        let help = self.keymap.help();
+        let keys = help
+            .iter()
+            .map(|(k, _)| k.as_str())
+            .collect::<Vec<&str>>()
+            .join("\n");
+        let descriptions = help
+            .iter()
+            .map(|(_, d)| *d)
+            .collect::<Vec<&str>>()
+            .join("\n");
+
+        let scale = PxScale::from(self.settings.header_font_size * 0.75);
+        let mut keys = Text::new(keys);
+        keys.set_scale(scale);
+        let mut descriptions = Text::new(descriptions);
+        descriptions.set_scale(scale);
+
+        let keys_size = keys.measure(ctx)?;
+        let descriptions_size = descriptions.measure(ctx)?;
+        let margin = 20.0;
+        let width = keys_size.x + descriptions_size.x + 3.0 * margin;
+        let height = keys_size.y.max(descriptions_size.y) + 2.0 * margin;
+        let origin = vec2(
+            (self.camera.window_size.0 - width) / 2.0,
+            (self.camera.window_size.1 - height) / 2.0,
+        );
+
+        let mut background = self.palette.background;
+        background.a = 0.9;
+        let panel = Mesh::new_rectangle(
+            ctx,
+            DrawMode::fill(),
+            Rect::new(origin.x, origin.y, width, height),
+            background,
+        )?;
+        canvas.draw(&panel, DrawParam::default());
+        let border = Mesh::new_rectangle(
+            ctx,
+            DrawMode::stroke(2.0),
+            Rect::new(origin.x, origin.y, width, height),
+            self.palette.line,
+        )?;
+        canvas.draw(&border, DrawParam::default());
+
+        canvas.draw(
+            &keys,
+            DrawParam::from(origin + vec2(margin, margin)).color(self.palette.text),
+        );
+        canvas.draw(
+            &descriptions,
+            DrawParam::from(origin + vec2(keys_size.x + 2.0 * margin, margin))
+                .color(self.palette.text),
+        );
+
+        Ok(())
+    }
+
+    // Load a pattern under its own rule, unless a rule was given.
+    pub fn load_rle(&mut self, filename: &str, rule: Option<Rule>) -> GameResult {
+        let mut injector = Injector::new(&mut self.universe, self.settings.backend, rule);
+        load_rle(filename, &mut injector, true)
+            .map_err(|e| GameError::CustomError(e.to_string()))?;
+
+        info!("Loaded pattern: {} cells", injector.cells);
+        self.edited();
+        Ok(())
+    }
+}
+
+impl EventHandler<GameError> for State {
+    fn update(&mut self, ctx: &mut Context) -> GameResult {
+        trace!("Update requested...");
+
+        if self.drag.is_none() && self.camera.coast(ctx.time.delta().as_secs_f64()) {
+            self.dirty = true;
+        }
+
+        if !self.running {
+            self.scheduler.stopped(Instant::now());
+            return Ok(());
+        }
+
+        let budget = self.scheduler.due(Instant::now());
+        let mut done = 0;
+        while done < budget.count
+            && (done == 0 || budget.deadline.is_none_or(|d| Instant::now() < d))
+        {
+            trace!("Update accepted...{}", self.universe.generation());
+            let us = now();
+
+            self.updates += 1;
+            if self.updates.is_multiple_of(self.scheduler.gps as u64) {
+                debug!("Updates: {}", self.updates);
+            }
+
+            self.step();
+            done += 1;
+
+            let ds = now() - us;
+            trace!("Update done: {ds} - {}", self.population());
+        }
+
+        if done > 0 {
+            self.scheduler.steps_done(done, Instant::now());
+            self.dirty = true;
+        }
+
+        Ok(())
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        trace!("Draw requested...");
+
+        self.draws += 1;
+        if self.draws.is_multiple_of(60) {
+            debug!("Draws: {}", self.draws);
+        }
+
+        trace!("Draw accepted...");
+        let start = now();
+
+        self.dirty = false;
+
+        let mut canvas = graphics::Canvas::from_frame(ctx, self.palette.background);
+
+        let camera = &self.camera;
+        let visible = camera.visible();
+        let (window_w, window_h) = camera.window_size;
+
+        // Lines are spaced out as the view zooms out, with stronger major lines.
+        if self.show_grid {
+            let spacing = camera.line_spacing();
+            let mut minor = self.palette.line;
+            minor.a *= 0.35;
+            let color = |c: i64| {
+                if c % (spacing * MAJOR_LINE_EVERY) == 0 {
+                    self.palette.line
+                } else {
+                    minor
+                }
+            };
+
+            let mut lb = MeshBuilder::new();
+            for x in Camera::lines(visible.min_x, visible.max_x, spacing) {
+                let sx = camera.grid_to_screen(x as f64, 0.0).x;
+                lb.line(&[vec2(sx, 0.0), vec2(sx, window_h)], 1.0, color(x))?;
+            }
+
+            for y in Camera::lines(visible.min_y, visible.max_y, spacing) {
+                let sy = camera.grid_to_screen(0.0, y as f64).y;
+                lb.line(&[vec2(0.0, sy), vec2(window_w, sy)], 1.0, color(y))?;
+            }
+
+            let mesh = lb.build();
+            canvas.draw(&Mesh::from_data(ctx, mesh), DrawParam::default());
+        }
+
+        let mut cells_drawn = 0;
+        if let Some(shift) = camera.shift() {
+            self.draw_density(ctx, &mut canvas, shift)?;
+        } else {
+            cells_drawn = self.draw_cells(ctx, &mut canvas)?;
+        }
+
+        if self.show_objects {
+            self.draw_objects(ctx, &mut canvas)?;
+        }
+        self.draw_selection(ctx, &mut canvas)?;
+
+        let pointer = ctx.mouse.position();
+        let pointer = self.camera.screen_to_grid(vec2(pointer.x, pointer.y));
+        let pointer = (pointer.0.floor() as i64, pointer.1.floor() as i64);
+        if self.show_rulers {
+            self.draw_rulers(ctx, &mut canvas, pointer)?;
+        }
+
+        if self.show_minimap {
+            self.draw_minimap(ctx, &mut canvas)?;
+        }
+
+        trace!("Draw finished: {} took {}", cells_drawn, now() - start);
+
+        if self.show_header {
+            let (gps, fps) = self.scheduler.rates(Instant::now());
+            let mut text = Text::new(format!(
+                "{}, Rule: {}, Seed: {}, Speed: {}, Step: 2^{}, GPS: {:.1}, FPS: {:.1}, Centre: ({:.0},{:.0}), Pointer: ({},{}), Cell size: {}, Generation: {}, Cells: {}, Period: {}{}",
+                if self.running { "Running" } else { "Stopped" },
+                self.universe.rule(),
+                self.seed,
+                self.scheduler.label(),
+                self.scheduler.step_exponent,
+                gps,
+                fps,
+                self.camera.centre.0,
+                self.camera.centre.1,
+                pointer.0,
+                pointer.1,
+                self.camera.zoom_label(),
+                self.universe.generation(),
+                self.population(),
+                match self.period.result() {
+                    Some(p) => p.to_string(),
+                    None => "-".to_owned(),
+                },
+                match &self.recorder {
+                    Some(recorder) => {
+                        let (frames, total) = recorder.progress();
+                        format!(", Recording: {frames}/{total}")
+                    }
+                    None => String::new(),
+                }
+            ));
+            text.set_scale(PxScale::from(self.settings.header_font_size));
+            canvas.draw(
+                &text,
+                graphics::DrawParam::from(Point2 { x: 0.0, y: 2.0 }).color(self.palette.text),
+            );
+
+            let entry = match &self.seed_entry {
+                Some(e) => Some(format!("Seed: {e}_ (Return to fill, Escape to cancel)")),
+                None if self.save_prompt => Some(self.save_prompt_text()),
+                None => None,
+            };
+            if let Some(notice) = entry.as_ref().or(self.notice.as_ref()) {
+                let mut text = Text::new(notice.as_str());
+                text.set_scale(PxScale::from(self.settings.header_font_size));
+                canvas.draw(
+                    &text,
+                    graphics::DrawParam::from(Point2 {
+                        x: 0.0,
+                        y: 2.0 + self.settings.header_font_size,
+                    })
+                    .color(self.palette.text),
+                );
+            }
+        }
+
+        if self.plot.visible {
+            let colors = PlotColors {
+                background: self.palette.background,
+                line: self.palette.line,
+                text: self.palette.text,
+                series: [self.palette.cell, self.palette.object],
+            };
+            self.plot.draw(
+                ctx,
+                &mut canvas,
+                &self.history,
+                &colors,
+                self.settings.header_font_size,
+                self.camera.window_size,
+            )?;
+        }
+
+        if self.show_help {
+            self.draw_help(ctx, &mut canvas)?;
+        }
+
+        canvas.finish(ctx)?;
+
+        let duration = now() - start;
+        trace!("Draw done: {duration}");
+        self.history
+            .record_draw(Duration::from_micros(duration as u64));
+
+        Ok(())
+    }
+
+    fn key_down_event(&mut self, ctx: &mut Context, input: KeyInput, repeat: bool) -> GameResult {
+        if let Some(keycode) = input.keycode {
+            let pan_delta = if input.mods.contains(KeyMods::SHIFT) {
+                100.0
+            } else {
+                10.0
+            };
+            // Pan by as many pixels zoomed out as at one pixel per cell.
+            let pan_delta = pan_delta / self.camera.cell_size.min(1.0);
+
+            // Any key press clears a previous notice.
+            self.notice = None;
+
+            if self.save_prompt {
+                self.save_prompt_key(keycode);
+                self.dirty = true;
+                return Ok(());
+            }
+            if self.seed_entry.is_some() {
+                self.seed_entry_key(keycode);
+                self.dirty = true;
+                return Ok(());
+            }
+
+            let action = self.keymap.lookup(keycode, input.mods);
+
+            // Moving the view by hand stops following.
+            if matches!(
+                action,
+                Some(
+                    Action::PanUp
+                        | Action::PanDown
+                        | Action::PanLeft
+                        | Action::PanRight
+                        | Action::Center
+                )
+            ) {
+                self.following = None;
+            }
+
+            match action {
+                Some(Action::ToggleRun) if !repeat => self.running ^= true,
+                Some(Action::Step) if !self.running => self.step(),
+                Some(Action::FasterGps) => self.scheduler.faster(),
+                Some(Action::SlowerGps) => self.scheduler.slower(),
+                Some(Action::CycleSpeed) => self.scheduler.speed = self.scheduler.speed.next(),
+                Some(Action::BiggerStep) => self.scheduler.bigger_step(),
+                Some(Action::SmallerStep) => self.scheduler.smaller_step(),
+                Some(Action::PanUp) => self.camera.pan_cells(0.0, -pan_delta),
+                Some(Action::PanDown) => self.camera.pan_cells(0.0, pan_delta),
+                Some(Action::PanLeft) => self.camera.pan_cells(-pan_delta, 0.0),
+                Some(Action::PanRight) => self.camera.pan_cells(pan_delta, 0.0),
+                Some(Action::Center) => {
+                    self.camera.stop();
+                    self.camera.centre = (0.0, 0.0);
+                }
+                Some(Action::Clear) => {
+                    self.universe = self.settings.backend.create(self.universe.rule());
+                    self.edited();
+                }
+                Some(Action::ToggleGrid) => self.show_grid = !self.show_grid,
+                Some(Action::ToggleRulers) if !repeat => self.show_rulers = !self.show_rulers,
+                Some(Action::ToggleHeader) => self.show_header = !self.show_header,
+                Some(Action::ToggleHelp) if !repeat => self.show_help = !self.show_help,
+                Some(Action::ToggleObjects) if !repeat => self.show_objects = !self.show_objects,
+                Some(Action::ToggleMinimap) if !repeat => self.show_minimap = !self.show_minimap,
+                Some(Action::CycleTheme) if !repeat => self.next_theme(),
+                Some(Action::CycleColors) if !repeat => {
+                    self.cell_colors = self.cell_colors.next();
+                    self.ages = None;
+                    self.notice = Some(format!("Cell colours: {}", self.cell_colors.name()));
+                }
+                Some(Action::TogglePlot) if !repeat => self.plot.visible = !self.plot.visible,
+                Some(Action::PlotZoomIn) => self.plot.zoom_in(),
+                Some(Action::PlotZoomOut) => self.plot.zoom_out(),
+                Some(Action::ExportStats) if !repeat => self.export_stats(),
+                Some(Action::SavePattern) if !repeat => self.save_pattern(),
+                Some(Action::ExportImage) if !repeat => match self.selection {
+                    Some(selection) => self.export_image(Some(selection), "the selection"),
+                    None => self.export_image(Some(self.camera.visible()), "the view"),
+                },
+                Some(Action::ToggleRecording) if !repeat => {
+                    if self.recorder.is_some() {
+                        self.finish_recording();
+                    } else {
+                        self.start_recording();
+                    }
+                }
+                Some(Action::ExportPatternImage) if !repeat => {
+                    self.export_image(self.universe.bounds(), "the pattern")
+                }
+                Some(Action::ZoomOut) => self.camera.zoom(-1),
+                Some(Action::ZoomIn) => self.camera.zoom(1),
+                Some(Action::FitPattern) => self.fit_pattern(),
+                Some(Action::FitSelection) => self.fit_selection(),
+                Some(Action::ClearSelection) => self.selection = None,
+                Some(Action::Follow) if !repeat => {
+                    let pointer = ctx.mouse.position();
+                    self.follow(vec2(pointer.x, pointer.y));
+                }
+                Some(Action::Randomize) => self.next_seed(),
+                Some(Action::Reroll) => self.seed_rand(),
+                Some(Action::EnterSeed) if !repeat => self.seed_entry = Some(String::new()),
+                Some(Action::SaveSettings) if !repeat => self.save_prompt = true,
+                _ => {}
+            }
+
+            self.dirty = true;
+        }
+
+        Ok(())
+    }
+
+    fn text_input_event(&mut self, _ctx: &mut Context, character: char) -> GameResult {
+        if let Some(entry) = &mut self.seed_entry {
+            if !character.is_control() && !character.is_whitespace() {
+                entry.push(character);
+                self.dirty = true;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn resize_event(
+        &mut self,
+        _ctx: &mut Context,
+        width: f32,
+        height: f32,
+    ) -> Result<(), GameError> {
+        debug!("Resize: {}, {}", width, height);
+
+        self.camera.resize_window(width, height);
+        self.dirty = true;
+        Ok(())
+    }
+
+    fn mouse_wheel_event(&mut self, ctx: &mut Context, _x: f32, y: f32) -> GameResult {
+        let cursor = ctx.mouse.position();
+        self.camera
+            .zoom_at(WHEEL_ZOOM.powf(y as f64), vec2(cursor.x, cursor.y));
+        self.dirty = true;
+
+        Ok(())
+    }
+
+    fn mouse_button_down_event(
+        &mut self,
+        ctx: &mut Context,
+        button: MouseButton,
+        x: f32,
+        y: f32,
+    ) -> GameResult {
+        if button == MouseButton::Left {
+            let p = vec2(x, y);
+            let layout = self.minimap_layout();
+
+            // Dragging on the minimap navigates, Shift-drag selects, otherwise dragging pans.
+            let mode = if self.show_minimap && layout.contains(p) {
+                self.following = None;
+                self.camera.centre = layout.screen_to_grid(p);
+                DragMode::Navigate(layout)
+            } else if ctx.keyboard.active_mods().contains(KeyMods::SHIFT) {
+                let from = self.camera.screen_to_grid(p);
+                self.selection = Some(cell_bounds(from, from));
+                DragMode::Select(from)
+            } else {
+                self.following = None;
+                DragMode::Pan
+            };
+
+            self.camera.stop();
+            self.drag = Some(Drag {
+                mode,
+                last: Instant::now(),
+                velocity: (0.0, 0.0),
+            });
+            self.dirty = true;
+        }
+
+        Ok(())
+    }
+
+    fn mouse_button_up_event(
+        &mut self,
+        _ctx: &mut Context,
+        button: MouseButton,
+        _x: f32,
+        _y: f32,
+    ) -> GameResult {
+        if button == MouseButton::Left {
+            if let Some(drag) = self.drag.take() {
+                // Released while still moving: keep scrolling.
+                if matches!(drag.mode, DragMode::Pan) && drag.last.elapsed() < FLING_WINDOW {
+                    self.camera.fling(drag.velocity.0, drag.velocity.1);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn mouse_motion_event(
+        &mut self,
+        _ctx: &mut Context,
+        x: f32,
+        y: f32,
+        dx: f32,
+        dy: f32,
+    ) -> GameResult {
+        let Some(drag) = &mut self.drag else {
+            // The pointer coordinates are shown in the header and on the rulers.
+            self.dirty |= self.show_header || self.show_rulers;
+            return Ok(());
+        };
+
+        match drag.mode {
+            DragMode::Pan => {
+                self.camera.drag(dx, dy);
+
+                let dt = drag.last.elapsed().as_secs_f64().max(0.001);
+                let (vx, vy) = (dx as f64 / dt, dy as f64 / dt);
+                drag.velocity = (
+                    0.5 * drag.velocity.0 + 0.5 * vx,
+                    0.5 * drag.velocity.1 + 0.5 * vy,
+                );
+                drag.last = Instant::now();
+            }
+            DragMode::Select(from) => {
+                let to = self.camera.screen_to_grid(vec2(x, y));
+                self.selection = Some(cell_bounds(from, to));
+            }
+            DragMode::Navigate(layout) => self.camera.centre = layout.screen_to_grid(vec2(x, y)),
+        }
+        self.dirty = true;
+
+        Ok(())
+    }
+}
+
+// Open the window and run the UI until it is closed.
+pub fn run(options: &Options, settings: Settings, layers: Layers) -> anyhow::Result<()> {
+    let (mut ctx, event_loop) = ContextBuilder::new("Rusty Life", "Frank Taylor")
+        .window_mode(
+            WindowMode::default()
+                .dimensions(options.window_size.0, options.window_size.1)
+                .resizable(true),
+        )
+        .build()?;
+
+    let mut state = State::new(&mut ctx, options, settings, layers)?;
+    if let Some(pattern) = &options.pattern {
+        info!("Loading pattern: {}", pattern);
+        state.load_rle(pattern, options.rule)?;
+    }
+
+    state.running = options.run;
+
+    run_events(ctx, event_loop, state);
+}
+
+// Like ggez's event::run, except frames are only drawn when something has changed, at most at
+// the maximum frame rate, and rather than polling the loop sleeps until the next input,
+// generation or kinetic scrolling frame.
+fn run_events(mut ctx: Context, event_loop: EventLoop<()>, mut state: State) -> ! {
+    event_loop.run(move |mut event, _, control_flow| {
+        let ctx = &mut ctx;
+        event::process_event(ctx, &mut event);
+
+        if let Err(e) = state.dispatch(ctx, event) {
+            error!("Stopping after error: {e}");
+            ctx.continuing = false;
+        }
+        if ctx.quit_requested {
+            ctx.continuing = false;
+        }
+
+        *control_flow = if ctx.continuing {
+            state.wake_time()
+        } else {
+            ControlFlow::Exit
+        };
+    })
+}
+
+impl State {
+    // Pass a window event to its handler, or update and draw once the pending events are handled.
+    fn dispatch(&mut self, ctx: &mut Context, event: Event<()>) -> GameResult {
+        match event {
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::Resized(size) => {
+                    self.resize_event(ctx, size.width as f32, size.height as f32)
+                }
+                WindowEvent::CloseRequested => {
+                    ctx.continuing = false;
+                    Ok(())
+                }
+                WindowEvent::ReceivedCharacter(character) => self.text_input_event(ctx, character),
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: keycode,
+                            scancode,
+                            ..
+                        },
+                    ..
+                } => {
+                    let input = KeyInput {
+                        scancode,
+                        keycode,
+                        mods: ctx.keyboard.active_mods(),
+                    };
+                    let repeat = ctx.keyboard.is_key_repeated();
+                    self.key_down_event(ctx, input, repeat)
+                }
+                WindowEvent::MouseWheel { delta, .. } => {
+                    let (x, y) = match delta {
+                        MouseScrollDelta::LineDelta(x, y) => (x, y),
+                        MouseScrollDelta::PixelDelta(p) => {
+                            let p = p.to_logical::<f32>(ctx.gfx.window().scale_factor());
+                            (p.x, p.y)
+                        }
+                    };
+                    self.mouse_wheel_event(ctx, x, y)
+                }
+                WindowEvent::MouseInput { state, button, .. } => {
+                    let p = ctx.mouse.position();
+                    match state {
+                        ElementState::Pressed => {
+                            self.mouse_button_down_event(ctx, button, p.x, p.y)
+                        }
+                        ElementState::Released => self.mouse_button_up_event(ctx, button, p.x, p.y),
+                    }
+                }
+                WindowEvent::CursorMoved { .. } => {
+                    let (p, delta) = (ctx.mouse.position(), ctx.mouse.last_delta());
+                    self.mouse_motion_event(ctx, p.x, p.y, delta.x, delta.y)
+                }
+                _ => Ok(()),
+            },
+            // The window was uncovered or otherwise needs drawing again.
+            Event::RedrawRequested(_) => {
+                self.dirty = true;
+                Ok(())
+            }
+            Event::MainEventsCleared => {
+                ctx.time.tick();
+                self.update(ctx)?;
+
+                if self.dirty && self.scheduler.frame_due(Instant::now()) {
+                    ctx.gfx.begin_frame()?;
+                    self.draw(ctx)?;
+                    ctx.gfx.end_frame()?;
+                    self.scheduler.frame_drawn(Instant::now());
+                }
+
+                ctx.mouse.reset_delta();
+                ctx.keyboard.save_keyboard_state();
+                ctx.mouse.save_mouse_state();
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // When the event loop should next wake if there's no input: for the next frame if there's a
+    // change still to draw or while scrolling, for the next generations while running, otherwise
+    // not at all.
+    fn wake_time(&self) -> ControlFlow {
+        let frame = (self.dirty || self.camera.is_moving()).then(|| self.scheduler.next_frame());
+        let generation = self.running.then(|| self.scheduler.next_generation());
+        match frame.into_iter().chain(generation).min() {
+            Some(time) => ControlFlow::WaitUntil(time),
+            None => ControlFlow::Wait,
+        }
+    }
+}
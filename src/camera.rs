@@ -0,0 +1,268 @@
+use ggez::glam::*;
+
+use boundlesse::grid::Bounds;
+
+// Zoom limits, from about a million cells per pixel across.
+const MIN_CELL_SIZE: f64 = 1.0 / (1 << 20) as f64;
+const MAX_CELL_SIZE: f64 = 256.0;
+
+// Fitting leaves this fraction of the window around the cells.
+const FIT_MARGIN: f64 = 0.1;
+
+// Grid lines are at least this many pixels apart, with every tenth a major line.
+const MIN_LINE_GAP: f64 = 6.0;
+pub const MAJOR_LINE_EVERY: i64 = 10;
+
+// Kinetic scrolling slows by this factor each second, and stops below MIN_SPEED pixels per second.
+const FRICTION: f64 = 0.05;
+const MIN_SPEED: f64 = 10.0;
+
+// The view onto the universe: the grid position at the centre of the window and the zoom.
+#[derive(Debug)]
+pub struct Camera {
+    // Reported window size in pixels.
+    pub window_size: (f32, f32),
+
+    // Grid position shown at the centre of the window.
+    pub centre: (f64, f64),
+
+    // Cell size in pixels, fractional and below 1 when zoomed out.
+    pub cell_size: f64,
+
+    // Kinetic scrolling velocity in pixels per second.
+    velocity: (f64, f64),
+}
+
+impl Camera {
+    pub fn new(window_size: (f32, f32), cell_size: f32) -> Self {
+        Camera {
+            window_size,
+            centre: (0.0, 0.0),
+            cell_size: (cell_size as f64).clamp(MIN_CELL_SIZE, MAX_CELL_SIZE),
+            velocity: (0.0, 0.0),
+        }
+    }
+
+    pub fn resize_window(&mut self, x: f32, y: f32) {
+        self.window_size = (x, y);
+    }
+
+    pub fn screen_to_grid(&self, p: Vec2) -> (f64, f64) {
+        (
+            self.centre.0 + (p.x as f64 - self.window_size.0 as f64 / 2.0) / self.cell_size,
+            self.centre.1 + (p.y as f64 - self.window_size.1 as f64 / 2.0) / self.cell_size,
+        )
+    }
+
+    pub fn grid_to_screen(&self, x: f64, y: f64) -> Vec2 {
+        vec2(
+            ((x - self.centre.0) * self.cell_size + self.window_size.0 as f64 / 2.0) as f32,
+            ((y - self.centre.1) * self.cell_size + self.window_size.1 as f64 / 2.0) as f32,
+        )
+    }
+
+    // The cells at least partly in the window.
+    pub fn visible(&self) -> Bounds {
+        let (x0, y0) = self.screen_to_grid(vec2(0.0, 0.0));
+        let (x1, y1) = self.screen_to_grid(vec2(self.window_size.0, self.window_size.1));
+        Bounds {
+            min_x: x0.floor() as i64,
+            min_y: y0.floor() as i64,
+            max_x: x1.ceil() as i64,
+            max_y: y1.ceil() as i64,
+        }
+    }
+
+    pub fn pan_cells(&mut self, dx: f64, dy: f64) {
+        self.centre.0 += dx;
+        self.centre.1 += dy;
+    }
+
+    // Move the view with the pointer, dragged by (dx, dy) pixels.
+    pub fn drag(&mut self, dx: f32, dy: f32) {
+        self.pan_cells(-dx as f64 / self.cell_size, -dy as f64 / self.cell_size);
+    }
+
+    // Zoom by a factor, keeping the grid position under the screen point fixed.
+    pub fn zoom_at(&mut self, factor: f64, p: Vec2) {
+        let (gx, gy) = self.screen_to_grid(p);
+        self.cell_size = (self.cell_size * factor).clamp(MIN_CELL_SIZE, MAX_CELL_SIZE);
+        self.centre = (
+            gx - (p.x as f64 - self.window_size.0 as f64 / 2.0) / self.cell_size,
+            gy - (p.y as f64 - self.window_size.1 as f64 / 2.0) / self.cell_size,
+        );
+    }
+
+    // Show all of the cells in the bounds, as large as they fit.
+    pub fn fit(&mut self, bounds: &Bounds) {
+        self.stop();
+        self.centre = (
+            (bounds.min_x + bounds.max_x + 1) as f64 / 2.0,
+            (bounds.min_y + bounds.max_y + 1) as f64 / 2.0,
+        );
+        let fit_x = self.window_size.0 as f64 / bounds.width() as f64;
+        let fit_y = self.window_size.1 as f64 / bounds.height() as f64;
+        self.cell_size =
+            (fit_x.min(fit_y) * (1.0 - FIT_MARGIN)).clamp(MIN_CELL_SIZE, MAX_CELL_SIZE);
+    }
+
+    // Zoom by powers of two about the centre, first snapping to the nearest.
+    pub fn zoom(&mut self, steps: i32) {
+        let exponent = self.cell_size.log2().round() as i32 + steps;
+        self.cell_size = 2f64.powi(exponent).clamp(MIN_CELL_SIZE, MAX_CELL_SIZE);
+    }
+
+    // Zoomed out to a pixel or less per cell, cells are drawn in blocks of 2^shift x 2^shift,
+    // each a pixel or a little more across.
+    pub fn shift(&self) -> Option<u32> {
+        (self.cell_size <= 1.0)
+            .then(|| (self.cell_size.recip().log2() - 1e-9).ceil().max(0.0) as u32)
+    }
+
+    // Cells between grid lines: the smallest power of ten keeping lines MIN_LINE_GAP apart.
+    pub fn line_spacing(&self) -> i64 {
+        let mut spacing: i64 = 1;
+        while (spacing as f64) * self.cell_size < MIN_LINE_GAP && spacing < i64::MAX / 100 {
+            spacing *= 10;
+        }
+        spacing
+    }
+
+    // Grid coordinates of the lines every `spacing` cells between min and max.
+    pub fn lines(min: i64, max: i64, spacing: i64) -> impl Iterator<Item = i64> {
+        let first = (min + spacing - 1).div_euclid(spacing) * spacing;
+        (0..)
+            .map(move |i| first + i * spacing)
+            .take_while(move |&x| x <= max)
+    }
+
+    pub fn zoom_label(&self) -> String {
+        if self.cell_size >= 1.0 {
+            format!("{:.2}", self.cell_size)
+        } else {
+            format!("1/{:.2}", self.cell_size.recip())
+        }
+    }
+
+    // Start kinetic scrolling at the pointer's velocity when a drag ends.
+    pub fn fling(&mut self, vx: f64, vy: f64) {
+        self.velocity = (vx, vy);
+    }
+
+    pub fn stop(&mut self) {
+        self.velocity = (0.0, 0.0);
+    }
+
+    pub fn is_moving(&self) -> bool {
+        self.velocity != (0.0, 0.0)
+    }
+
+    // Continue kinetic scrolling for dt seconds, returning true if the view moved.
+    pub fn coast(&mut self, dt: f64) -> bool {
+        let (vx, vy) = self.velocity;
+        if vx.hypot(vy) < MIN_SPEED {
+            self.stop();
+            return false;
+        }
+
+        self.drag((vx * dt) as f32, (vy * dt) as f32);
+        let decay = FRICTION.powf(dt);
+        self.velocity = (vx * decay, vy * decay);
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn screen_and_grid() {
+        let mut camera = Camera::new((800.0, 600.0), 10.0);
+
+        assert_eq!(camera.screen_to_grid(vec2(400.0, 300.0)), (0.0, 0.0));
+        assert_eq!(camera.screen_to_grid(vec2(0.0, 0.0)), (-40.0, -30.0));
+        assert_eq!(camera.grid_to_screen(1.0, 2.0), vec2(410.0, 320.0));
+
+        let visible = camera.visible();
+        assert_eq!((visible.min_x, visible.max_y), (-40, 30));
+
+        // Dragging right moves the view left.
+        camera.drag(20.0, 0.0);
+        assert_eq!(camera.centre, (-2.0, 0.0));
+    }
+
+    #[test]
+    fn zoom_at_cursor() {
+        let mut camera = Camera::new((800.0, 600.0), 10.0);
+        let cursor = vec2(600.0, 100.0);
+        let before = camera.screen_to_grid(cursor);
+
+        camera.zoom_at(1.5, cursor);
+        assert_eq!(camera.cell_size, 15.0);
+        let after = camera.screen_to_grid(cursor);
+        assert!((after.0 - before.0).abs() < 1e-9 && (after.1 - before.1).abs() < 1e-9);
+
+        // Key zooming snaps to powers of two.
+        camera.zoom(-1);
+        assert_eq!(camera.cell_size, 8.0);
+        camera.zoom(-4);
+        assert_eq!((camera.cell_size, camera.shift()), (0.5, Some(1)));
+
+        // Blocks are never smaller than a pixel.
+        camera.zoom_at(0.75, cursor);
+        assert_eq!(camera.shift(), Some(2));
+    }
+
+    #[test]
+    fn fit_bounds() {
+        let mut camera = Camera::new((800.0, 600.0), 10.0);
+        let bounds = Bounds {
+            min_x: 100,
+            min_y: -50,
+            max_x: 299,
+            max_y: 49,
+        };
+        camera.fit(&bounds);
+
+        assert_eq!(camera.centre, (200.0, 0.0));
+        assert!((camera.cell_size - 3.6).abs() < 1e-9);
+        let visible = camera.visible();
+        assert!(visible.min_x < 100 && visible.max_x > 299);
+    }
+
+    #[test]
+    fn grid_lines() {
+        let mut camera = Camera::new((800.0, 600.0), 10.0);
+        assert_eq!(camera.line_spacing(), 1);
+        camera.cell_size = 2.0;
+        assert_eq!(camera.line_spacing(), 10);
+        camera.cell_size = 1.0 / 64.0;
+        assert_eq!(camera.line_spacing(), 1000);
+
+        // Lines fall on multiples of the spacing, whichever side of the origin.
+        let lines: Vec<i64> = Camera::lines(-25, 12, 10).collect();
+        assert_eq!(lines, vec![-20, -10, 0, 10]);
+        assert_eq!(Camera::lines(1, 9, 10).count(), 0);
+        assert_eq!(Camera::lines(0, 9, 10).collect::<Vec<_>>(), vec![0]);
+    }
+
+    #[test]
+    fn kinetic_scrolling() {
+        let mut camera = Camera::new((800.0, 600.0), 1.0);
+        camera.fling(100.0, 0.0);
+        assert!(camera.is_moving());
+
+        assert!(camera.coast(0.1));
+        assert!(camera.centre.0 < 0.0);
+
+        // Friction eventually stops it.
+        let mut frames = 0;
+        while camera.coast(0.1) {
+            frames += 1;
+        }
+        assert!(frames < 100);
+        assert!(!camera.is_moving());
+    }
+}
@@ -0,0 +1,644 @@
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgAction, Command, ErrorKind};
+use std::time::Duration;
+
+use crate::settings::{Settings, Speed, MAX_STEP_EXPONENT};
+use boundlesse::grid::{Backend, Bounds};
+use boundlesse::headless::{Batch, StopCondition};
+use boundlesse::objects::Separation;
+use boundlesse::record::Recording;
+use boundlesse::render::Style;
+use boundlesse::rule::Rule;
+use boundlesse::soup::{random_seed, Fill, Search, Symmetry, DEFAULT_SOUP_SIZE};
+
+pub const DEFAULT_WINDOW_SIZE: (f32, f32) = (2000.0, 1500.0);
+pub const DEFAULT_CELL_SIZE: f32 = 10.0;
+pub const DEFAULT_PATTERN_SIZE: (i64, i64) = (200, 200);
+pub const DEFAULT_DENSITY: f64 = 0.5;
+
+// Options left as None fall back to the settings files.
+#[derive(Debug)]
+pub struct Options {
+    pub pattern: Option<String>,
+    pub config: Option<String>,
+    pub speed: Option<Speed>,
+    pub gps: Option<u32>,
+    pub gens_per_frame: Option<u32>,
+    pub max_fps: Option<u32>,
+    pub step_exponent: Option<u32>,
+    pub window_size: (f32, f32),
+    pub cell_size: f32,
+    pub rule: Option<Rule>,
+    pub backend: Option<Backend>,
+    pub pattern_size: (i64, i64),
+    pub seed: String,
+    pub density: f64,
+    pub symmetry: Symmetry,
+    pub run: bool,
+    pub headless: Option<Batch>,
+    pub threads: Option<usize>,
+    pub image_cell_size: Option<f64>,
+    pub record_frames: Option<usize>,
+    pub record_step: Option<usize>,
+    pub record_delay: Option<u64>,
+    // The rule, backend and threads of the search are filled in from the settings.
+    pub soup_search: Option<Search>,
+    pub census: Option<String>,
+}
+
+impl Options {
+    pub fn override_settings(&self, settings: &mut Settings) {
+        if let Some(speed) = self.speed {
+            settings.speed = speed;
+        }
+        if let Some(gps) = self.gps {
+            settings.gps = gps;
+        }
+        if let Some(n) = self.gens_per_frame {
+            settings.gens_per_frame = n;
+        }
+        if let Some(fps) = self.max_fps {
+            settings.max_fps = fps;
+        }
+        if let Some(k) = self.step_exponent {
+            settings.step_exponent = k;
+        }
+        if let Some(rule) = self.rule {
+            settings.rule = rule;
+        }
+        if let Some(backend) = self.backend {
+            settings.backend = backend;
+        }
+        if let Some(threads) = self.threads {
+            settings.threads = threads;
+        }
+        if let Some(size) = self.image_cell_size {
+            settings.image_cell_size = size;
+        }
+        if let Some(frames) = self.record_frames {
+            settings.record_frames = frames;
+        }
+        if let Some(step) = self.record_step {
+            settings.record_step = step;
+        }
+        if let Some(delay) = self.record_delay {
+            settings.record_delay = delay;
+        }
+    }
+}
+
+// Parse a count that must be at least 1, e.g. a rate or a number of frames.
+fn parse_positive<T>(s: &str) -> Result<T>
+where
+    T: std::str::FromStr + PartialOrd + Default,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    let n = s.parse::<T>()?;
+    if n <= T::default() {
+        return Err(anyhow!("must be at least 1"));
+    }
+
+    Ok(n)
+}
+
+fn parse_step_exponent(s: &str) -> Result<u32> {
+    let k = s.parse::<u32>()?;
+    if k > MAX_STEP_EXPONENT {
+        return Err(anyhow!("must be at most {MAX_STEP_EXPONENT}"));
+    }
+
+    Ok(k)
+}
+
+fn parse_speed(s: &str) -> Result<Speed> {
+    match s {
+        "gps" => Ok(Speed::Gps),
+        "per-frame" => Ok(Speed::PerFrame),
+        "max" => Ok(Speed::Max),
+        _ => Err(anyhow!("expected gps, per-frame or max")),
+    }
+}
+
+fn parse_cell_size(s: &str) -> Result<f64> {
+    let size = s.parse::<f64>()?;
+    if !(size.is_finite() && size > 0.0) {
+        return Err(anyhow!("must be positive"));
+    }
+
+    Ok(size)
+}
+
+// Parse a size in the form WIDTHxHEIGHT, e.g. 800x600.
+fn parse_size<T>(s: &str) -> Result<(T, T)>
+where
+    T: std::str::FromStr + PartialOrd + Default,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    let (w, h) = s.split_once('x').ok_or(anyhow!("expected WIDTHxHEIGHT"))?;
+    let (w, h) = (w.trim().parse::<T>()?, h.trim().parse::<T>()?);
+    if w <= T::default() || h <= T::default() {
+        return Err(anyhow!("width and height must be positive"));
+    }
+
+    Ok((w, h))
+}
+
+fn parse_window_size(s: &str) -> Result<(f32, f32)> {
+    parse_size(s)
+}
+
+fn parse_pattern_size(s: &str) -> Result<(i64, i64)> {
+    parse_size(s)
+}
+
+// Parse a region in the form X,Y,WIDTHxHEIGHT, e.g. -10,-10,40x30.
+fn parse_region(s: &str) -> Result<Bounds> {
+    let mut parts = s.splitn(3, ',');
+    let (Some(x), Some(y), Some(size)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(anyhow!("expected X,Y,WIDTHxHEIGHT"));
+    };
+    let (x, y) = (x.trim().parse::<i64>()?, y.trim().parse::<i64>()?);
+    let (w, h) = parse_size::<i64>(size)?;
+
+    Ok(Bounds {
+        min_x: x,
+        min_y: y,
+        max_x: x + w - 1,
+        max_y: y + h - 1,
+    })
+}
+
+fn parse_rule(s: &str) -> Result<Rule> {
+    s.parse()
+}
+
+fn parse_backend(s: &str) -> Result<Backend> {
+    s.parse()
+}
+
+fn parse_soup_size(s: &str) -> Result<(i64, i64)> {
+    parse_size(s)
+}
+
+fn parse_density(s: &str) -> Result<f64> {
+    let density = s.parse::<f64>()?;
+    if !(0.0..=1.0).contains(&density) {
+        return Err(anyhow!("must be between 0 and 1"));
+    }
+
+    Ok(density)
+}
+
+fn parse_symmetry(s: &str) -> Result<Symmetry> {
+    s.parse()
+}
+
+fn parse_condition(s: &str) -> Result<StopCondition> {
+    s.parse()
+}
+
+fn command() -> Command<'static> {
+    Command::new("boundlesse")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("A boundless Game of Life simulation")
+        .arg(
+            Arg::new("pattern")
+                .value_name("PATTERN")
+                .help("RLE pattern file to load into the universe"),
+        )
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .value_name("FILE")
+                .help("Settings file, overriding the per-user and per-project settings"),
+        )
+        .arg(
+            Arg::new("speed")
+                .long("speed")
+                .value_name("MODE")
+                .value_parser(parse_speed)
+                .help("How fast to run: gps, per-frame or max (as fast as possible) [default: from settings]"),
+        )
+        .arg(
+            Arg::new("gps")
+                .long("gps")
+                .value_name("N")
+                .value_parser(parse_positive::<u32>)
+                .help("Target generations per second [default: from settings]"),
+        )
+        .arg(
+            Arg::new("gens-per-frame")
+                .long("gens-per-frame")
+                .value_name("N")
+                .value_parser(parse_positive::<u32>)
+                .help("Generations per frame at the per-frame speed [default: from settings]"),
+        )
+        .arg(
+            Arg::new("max-fps")
+                .long("max-fps")
+                .value_name("N")
+                .value_parser(parse_positive::<u32>)
+                .help("Maximum frames drawn per second [default: from settings]"),
+        )
+        .arg(
+            Arg::new("step-exponent")
+                .long("step-exponent")
+                .value_name("K")
+                .value_parser(parse_step_exponent)
+                .help("Advance 2^K generations in each step [default: from settings]"),
+        )
+        .arg(
+            Arg::new("window-size")
+                .long("window-size")
+                .value_name("WxH")
+                .value_parser(parse_window_size)
+                .help("Initial window size in pixels [default: 2000x1500]"),
+        )
+        .arg(
+            Arg::new("cell-size")
+                .long("cell-size")
+                .value_name("PX")
+                .value_parser(parse_cell_size)
+                .help("Initial cell size in pixels, below 1 when zoomed out [default: 10]"),
+        )
+        .arg(
+            Arg::new("rule")
+                .long("rule")
+                .value_name("RULE")
+                .value_parser(parse_rule)
+                .help("Life-like rule in B/S, or Generations rule in B/S/C notation [default: from settings]"),
+        )
+        .arg(
+            Arg::new("backend")
+                .long("backend")
+                .value_name("BACKEND")
+                .value_parser(parse_backend)
+                .help("Universe implementation: generations or naive [default: from settings]"),
+        )
+        .arg(
+            Arg::new("pattern-size")
+                .long("pattern-size")
+                .value_name("WxH")
+                .value_parser(parse_pattern_size)
+                .help("Size in cells of the random soups created by 'r' [default: 200x200]"),
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .value_name("SEED")
+                .help("Seed for random soups, the prefix for soup search [default: random]"),
+        )
+        .arg(
+            Arg::new("density")
+                .long("density")
+                .value_name("D")
+                .value_parser(parse_density)
+                .help("Chance of each cell being alive in random soups [default: 0.5]"),
+        )
+        .arg(
+            Arg::new("symmetry")
+                .long("symmetry")
+                .value_name("SYM")
+                .value_parser(parse_symmetry)
+                .help("Symmetry of random soups: C1, C2, C4, D2, D4 or D8 [default: C1]"),
+        )
+        .arg(
+            Arg::new("run")
+                .long("run")
+                .action(ArgAction::SetTrue)
+                .help("Start the simulation running"),
+        )
+        .arg(
+            Arg::new("headless")
+                .long("headless")
+                .action(ArgAction::SetTrue)
+                .help("Run without a window, reporting progress to stdout"),
+        )
+        .arg(
+            Arg::new("generations")
+                .long("generations")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .requires("headless")
+                .help("Headless: stop after N generations"),
+        )
+        .arg(
+            Arg::new("until")
+                .long("until")
+                .value_name("CONDITION")
+                .value_parser(parse_condition)
+                .requires("headless")
+                .help("Headless: stop when empty, periodic, population>N or population<N"),
+        )
+        .arg(
+            Arg::new("report-every")
+                .long("report-every")
+                .value_name("N")
+                .value_parser(parse_positive::<usize>)
+                .requires("headless")
+                .help("Headless: report every N generations [default: 100]"),
+        )
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .value_name("FILE")
+                .requires("headless")
+                .help("Headless: write the final pattern as RLE to FILE"),
+        )
+        .arg(
+            Arg::new("stats")
+                .long("stats")
+                .value_name("FILE")
+                .requires("headless")
+                .help("Headless: write the statistics of every generation as CSV to FILE"),
+        )
+        .arg(
+            Arg::new("image")
+                .long("image")
+                .value_name("FILE")
+                .requires("headless")
+                .help("Headless: draw the final pattern as PNG to FILE"),
+        )
+        .arg(
+            Arg::new("image-cell-size")
+                .long("image-cell-size")
+                .value_name("N")
+                .value_parser(parse_cell_size)
+                .help("Pixels per cell in exported images, below 1 to shrink [default: from settings]"),
+        )
+        .arg(
+            Arg::new("record")
+                .long("record")
+                .value_name("FILE")
+                .requires("headless")
+                .help("Headless: record the run as an animated GIF, or numbered PNGs for FILE.png"),
+        )
+        .arg(
+            Arg::new("record-region")
+                .long("record-region")
+                .value_name("X,Y,WxH")
+                .value_parser(parse_region)
+                .allow_hyphen_values(true)
+                .requires("record")
+                .help("Headless: cells recorded [default: all those the pattern reaches]"),
+        )
+        .arg(
+            Arg::new("record-frames")
+                .long("record-frames")
+                .value_name("N")
+                .value_parser(parse_positive::<usize>)
+                .requires("record")
+                .help("Headless: frames in recordings [default: from settings]"),
+        )
+        .arg(
+            Arg::new("record-step")
+                .long("record-step")
+                .value_name("N")
+                .value_parser(parse_positive::<usize>)
+                .requires("record")
+                .help("Headless: generations between recorded frames [default: from settings]"),
+        )
+        .arg(
+            Arg::new("record-delay")
+                .long("record-delay")
+                .value_name("MS")
+                .value_parser(clap::value_parser!(u64))
+                .requires("record")
+                .help("Headless: milliseconds each recorded frame is shown [default: from settings]"),
+        )
+        .arg(
+            Arg::new("objects")
+                .long("objects")
+                .action(ArgAction::SetTrue)
+                .requires("headless")
+                .help("Headless: list the objects in the final pattern"),
+        )
+        .arg(
+            Arg::new("threads")
+                .long("threads")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .help("Worker threads, 0 for one per core [default: from settings]"),
+        )
+        .arg(
+            Arg::new("soup-search")
+                .long("soup-search")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(&["headless", "pattern"])
+                .help("Run random soups to stabilisation and report a census of the objects left"),
+        )
+        .arg(
+            Arg::new("soups")
+                .long("soups")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .requires("soup-search")
+                .help("Soup search: number of soups to run [default: 1000]"),
+        )
+        .arg(
+            Arg::new("soup-size")
+                .long("soup-size")
+                .value_name("WxH")
+                .value_parser(parse_soup_size)
+                .requires("soup-search")
+                .help("Soup search: size in cells of each soup [default: 16x16]"),
+        )
+        .arg(
+            Arg::new("census")
+                .long("census")
+                .value_name("FILE")
+                .requires("soup-search")
+                .help("Soup search: write the census to FILE [default: stdout]"),
+        )
+}
+
+// Parse the command line, exiting with usage on error or --help/--version.
+pub fn parse() -> Options {
+    let matches = command().get_matches();
+
+    let seed = matches
+        .get_one::<String>("seed")
+        .cloned()
+        .unwrap_or_else(random_seed);
+    let density = matches
+        .get_one("density")
+        .copied()
+        .unwrap_or(DEFAULT_DENSITY);
+    let symmetry = matches.get_one("symmetry").copied().unwrap_or_default();
+
+    Options {
+        pattern: matches.get_one::<String>("pattern").cloned(),
+        config: matches.get_one::<String>("config").cloned(),
+        speed: matches.get_one("speed").copied(),
+        gps: matches.get_one("gps").copied(),
+        gens_per_frame: matches.get_one("gens-per-frame").copied(),
+        max_fps: matches.get_one("max-fps").copied(),
+        step_exponent: matches.get_one("step-exponent").copied(),
+        window_size: matches
+            .get_one("window-size")
+            .copied()
+            .unwrap_or(DEFAULT_WINDOW_SIZE),
+        cell_size: matches
+            .get_one::<f64>("cell-size")
+            .map_or(DEFAULT_CELL_SIZE, |&size| size as f32),
+        rule: matches.get_one("rule").copied(),
+        backend: matches.get_one("backend").copied(),
+        pattern_size: matches
+            .get_one("pattern-size")
+            .copied()
+            .unwrap_or(DEFAULT_PATTERN_SIZE),
+        seed: seed.clone(),
+        density,
+        symmetry,
+        run: matches.get_one::<bool>("run").copied().unwrap_or(false),
+        headless: if matches
+            .get_one::<bool>("headless")
+            .copied()
+            .unwrap_or(false)
+        {
+            Some(Batch {
+                generations: matches.get_one("generations").copied(),
+                until: matches.get_one("until").copied(),
+                report_every: matches.get_one("report-every").copied().unwrap_or(100),
+                output: matches.get_one::<String>("output").cloned(),
+                stats: matches.get_one::<String>("stats").cloned(),
+                image: matches.get_one::<String>("image").cloned(),
+                // Filled in from the settings.
+                image_style: Style::default(),
+                // Filled in from the settings, apart from the file and region.
+                record: matches.get_one::<String>("record").map(|path| Recording {
+                    path: path.clone(),
+                    region: matches.get_one("record-region").copied(),
+                    frames: 0,
+                    step: 0,
+                    style: Style::default(),
+                    delay: Duration::ZERO,
+                }),
+                // Filled in from the settings.
+                objects: matches
+                    .get_one::<bool>("objects")
+                    .copied()
+                    .unwrap_or(false)
+                    .then(Separation::default),
+            })
+        } else {
+            None
+        },
+        threads: matches.get_one("threads").copied(),
+        image_cell_size: matches.get_one("image-cell-size").copied(),
+        record_frames: matches.get_one("record-frames").copied(),
+        record_step: matches.get_one("record-step").copied(),
+        record_delay: matches.get_one("record-delay").copied(),
+        soup_search: if matches
+            .get_one::<bool>("soup-search")
+            .copied()
+            .unwrap_or(false)
+        {
+            Some(Search {
+                rule: Rule::default(),
+                backend: Backend::default(),
+                soups: matches.get_one("soups").copied().unwrap_or(1000),
+                fill: Fill::new(
+                    matches
+                        .get_one("soup-size")
+                        .copied()
+                        .unwrap_or(DEFAULT_SOUP_SIZE),
+                    density,
+                    symmetry,
+                )
+                .unwrap_or_else(|e| command().error(ErrorKind::ValueValidation, e).exit()),
+                seed,
+                threads: 0,
+            })
+        } else {
+            None
+        },
+        census: matches.get_one::<String>("census").cloned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_sizes() -> Result<()> {
+        assert_eq!(parse_window_size("800x600")?, (800.0, 600.0));
+        assert_eq!(parse_pattern_size("16x16")?, (16, 16));
+
+        assert!(parse_pattern_size("16").is_err());
+        assert!(parse_pattern_size("0x16").is_err());
+        assert!(parse_window_size("axb").is_err());
+
+        assert_eq!(parse_cell_size("0.25")?, 0.25);
+        assert_eq!(parse_cell_size("0.1")?, 0.1);
+        assert!(parse_cell_size("0").is_err());
+        assert!(parse_cell_size("-2").is_err());
+        assert!(parse_cell_size("inf").is_err());
+        assert!(parse_cell_size("NaN").is_err());
+
+        let region = parse_region("-10,5,40x30")?;
+        assert_eq!(
+            (region.min_x, region.min_y, region.max_x, region.max_y),
+            (-10, 5, 29, 34)
+        );
+        assert!(parse_region("-10,5").is_err());
+        assert!(parse_region("1,2,0x3").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn reject_bad_flag() {
+        let err = command()
+            .try_get_matches_from(["boundlesse", "--gps", "0"])
+            .expect_err("GPS of 0 accepted");
+
+        assert!(err.to_string().contains("--gps"));
+
+        assert!(command()
+            .try_get_matches_from(["boundlesse", "--speed", "fast"])
+            .is_err());
+        assert!(command()
+            .try_get_matches_from(["boundlesse", "--step-exponent", "21"])
+            .is_err());
+        let matches = command().try_get_matches_from(["boundlesse", "--speed", "per-frame"]);
+        assert_eq!(
+            matches.unwrap().get_one::<Speed>("speed"),
+            Some(&Speed::PerFrame)
+        );
+    }
+
+    #[test]
+    fn headless_flags_require_headless() {
+        assert!(command()
+            .try_get_matches_from(["boundlesse", "--generations", "10"])
+            .is_err());
+        assert!(command()
+            .try_get_matches_from(["boundlesse", "--headless", "--generations", "10"])
+            .is_ok());
+        assert!(command()
+            .try_get_matches_from(["boundlesse", "--headless", "--record-frames", "10"])
+            .is_err());
+    }
+
+    #[test]
+    fn soup_search_flags() {
+        assert!(command()
+            .try_get_matches_from(["boundlesse", "--soups", "10"])
+            .is_err());
+        assert!(command()
+            .try_get_matches_from(["boundlesse", "--soup-search", "--headless"])
+            .is_err());
+        assert!(command()
+            .try_get_matches_from([
+                "boundlesse",
+                "--soup-search",
+                "--soups",
+                "10",
+                "--seed",
+                "x"
+            ])
+            .is_ok());
+    }
+}
@@ -0,0 +1,168 @@
+//! Counting live cells in power-of-two blocks, for drawing zoomed out views.
+
+use std::collections::{HashMap, HashSet};
+
+/// Live cell counts in blocks of 2^shift x 2^shift cells, for shifts from a base up.
+///
+/// Only the base counts follow cells as they are added and removed. Each larger level is built
+/// from the one below when first needed, and afterwards brought up to date from the base blocks
+/// changed since, when next counted.
+#[derive(Debug)]
+pub struct Density {
+    base: u32,
+    // Level n maps block (x >> (base + n), y >> (base + n)) to its live cell count.
+    levels: Vec<HashMap<(i64, i64), u32>>,
+    // Base blocks changed since the larger levels were last brought up to date.
+    dirty: HashSet<(i64, i64)>,
+}
+
+impl Density {
+    /// Counts built from those of the blocks at the base shift.
+    pub fn new(base: u32, blocks: HashMap<(i64, i64), u32>) -> Self {
+        Density {
+            base,
+            levels: vec![blocks],
+            dirty: HashSet::new(),
+        }
+    }
+
+    pub fn add(&mut self, x: i64, y: i64) {
+        let block = (x >> self.base, y >> self.base);
+        *self.levels[0].entry(block).or_insert(0) += 1;
+        self.changed(block);
+    }
+
+    pub fn remove(&mut self, x: i64, y: i64) {
+        let block = (x >> self.base, y >> self.base);
+        if let Some(count) = self.levels[0].get_mut(&block) {
+            *count -= 1;
+            if *count == 0 {
+                self.levels[0].remove(&block);
+            }
+            self.changed(block);
+        }
+    }
+
+    fn changed(&mut self, block: (i64, i64)) {
+        if self.levels.len() == 1 {
+            return;
+        }
+        self.dirty.insert(block);
+        // Once most blocks have changed, building the larger levels afresh costs no more, so
+        // drop them rather than let the changes pile up while they aren't counted.
+        if self.dirty.len() > self.levels[0].len() {
+            self.levels.truncate(1);
+            self.dirty.clear();
+        }
+    }
+
+    // Recount the blocks of the larger levels over the changed base blocks, level by level.
+    fn refresh(&mut self) {
+        let mut changed = std::mem::take(&mut self.dirty);
+        for n in 1..self.levels.len() {
+            changed = changed.iter().map(|&(x, y)| (x >> 1, y >> 1)).collect();
+            let (below, above) = self.levels.split_at_mut(n);
+            let (below, level) = (&below[n - 1], &mut above[0]);
+            for &(x, y) in &changed {
+                let count: u32 = [(0, 0), (1, 0), (0, 1), (1, 1)]
+                    .iter()
+                    .filter_map(|&(dx, dy)| below.get(&(2 * x + dx, 2 * y + dy)))
+                    .sum();
+                if count == 0 {
+                    level.remove(&(x, y));
+                } else {
+                    level.insert((x, y), count);
+                }
+            }
+        }
+    }
+
+    fn level(&mut self, shift: u32) -> &HashMap<(i64, i64), u32> {
+        self.refresh();
+
+        let n = shift.saturating_sub(self.base) as usize;
+        while self.levels.len() <= n {
+            let below = self.levels.last().expect("Base level always present");
+            let mut level = HashMap::with_capacity(below.len() / 2);
+            for (&(x, y), &count) in below {
+                *level.entry((x >> 1, y >> 1)).or_insert(0) += count;
+            }
+            self.levels.push(level);
+        }
+
+        &self.levels[n]
+    }
+
+    /// Counts for the `size` blocks of 2^shift cells starting at block `origin`, row by row.
+    pub fn counts(&mut self, shift: u32, origin: (i64, i64), size: (usize, usize)) -> Vec<u32> {
+        let (w, h) = size;
+        let mut counts = vec![0; w * h];
+        let level = self.level(shift);
+
+        // Visit whichever is smaller: the occupied blocks or the area asked for.
+        if level.len() < counts.len() {
+            for (&(x, y), &count) in level {
+                let (dx, dy) = (x - origin.0, y - origin.1);
+                if dx >= 0 && dy >= 0 && (dx as usize) < w && (dy as usize) < h {
+                    counts[dy as usize * w + dx as usize] = count;
+                }
+            }
+        } else {
+            for (dy, row) in counts.chunks_mut(w.max(1)).enumerate() {
+                for (dx, count) in row.iter_mut().enumerate() {
+                    let block = (origin.0 + dx as i64, origin.1 + dy as i64);
+                    *count = level.get(&block).copied().unwrap_or(0);
+                }
+            }
+        }
+
+        counts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn block_counts() {
+        let cells = [(0, 0), (1, 1), (2, 0), (-1, -1), (-4, 3)];
+        let mut density = Density::new(0, cells.iter().map(|&c| (c, 1)).collect());
+
+        assert_eq!(
+            density.counts(0, (-1, -1), (3, 3)),
+            vec![1, 0, 0, 0, 1, 0, 0, 0, 1]
+        );
+
+        // Negative coordinates round down into their blocks.
+        assert_eq!(
+            density.counts(1, (-2, -1), (4, 3)),
+            vec![0, 1, 0, 0, 0, 0, 2, 1, 1, 0, 0, 0]
+        );
+        assert_eq!(density.counts(2, (-1, 0), (2, 1)), vec![1, 3]);
+
+        // Every cell ends up in one block at the top.
+        let total: u32 = density.counts(8, (-1, -1), (2, 2)).iter().sum();
+        assert_eq!(total, 5);
+
+        // Built levels catch up with cells as they come and go.
+        density.remove(0, 0);
+        density.remove(1, 1);
+        density.add(3, 1);
+        assert_eq!(density.counts(2, (-1, 0), (2, 1)), vec![1, 2]);
+        assert_eq!(
+            density.counts(1, (-2, -1), (4, 3)),
+            vec![0, 1, 0, 0, 0, 0, 0, 2, 1, 0, 0, 0]
+        );
+        let total: u32 = density.counts(8, (-1, -1), (2, 2)).iter().sum();
+        assert_eq!(total, 4);
+
+        // Changing most blocks drops the larger levels, to be built again when counted.
+        density.remove(2, 0);
+        density.remove(-1, -1);
+        density.remove(-4, 3);
+        density.add(5, 5);
+        assert_eq!(density.levels.len(), 1);
+        assert_eq!(density.counts(2, (0, 0), (2, 2)), vec![1, 0, 0, 1]);
+    }
+}
@@ -1,12 +1,23 @@
-use std::collections::HashMap;
+//! Universe implementations and the cell coordinate types.
 
-#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::density::Density;
+use crate::rule::Rule;
+
+/// A cell position in the universe.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone, Debug)]
 pub enum GridCoord {
     Valid(i64, i64),
     OutOfBounds,
 }
 
 impl GridCoord {
+    /// The coordinate offset by (a, b).
     pub fn adjust(&self, a: i64, b: i64) -> GridCoord {
         // TODO Implement OutOfBounds
 
@@ -17,6 +28,7 @@ impl GridCoord {
         }
     }
 
+    /// The coordinate and its eight neighbours.
     pub fn expand(&self) -> [GridCoord; 9] {
         if let GridCoord::Valid(_, _) = self {
             [
@@ -36,10 +48,420 @@ impl GridCoord {
     }
 }
 
+/// Inclusive bounding box of a set of cells.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct Bounds {
+    pub min_x: i64,
+    pub min_y: i64,
+    pub max_x: i64,
+    pub max_y: i64,
+}
+
+impl Bounds {
+    /// Bounds of the cells, or None if there are none.
+    pub fn of(cells: &[GridCoord]) -> Option<Bounds> {
+        let mut bounds: Option<Bounds> = None;
+        for c in cells {
+            if let GridCoord::Valid(x, y) = *c {
+                match &mut bounds {
+                    Some(b) => b.include(x, y),
+                    None => {
+                        bounds = Some(Bounds {
+                            min_x: x,
+                            min_y: y,
+                            max_x: x,
+                            max_y: y,
+                        })
+                    }
+                }
+            }
+        }
+
+        bounds
+    }
+
+    pub fn include(&mut self, x: i64, y: i64) {
+        self.min_x = self.min_x.min(x);
+        self.min_y = self.min_y.min(y);
+        self.max_x = self.max_x.max(x);
+        self.max_y = self.max_y.max(y);
+    }
+
+    pub fn contains(&self, x: i64, y: i64) -> bool {
+        x >= self.min_x && x <= self.max_x && y >= self.min_y && y <= self.max_y
+    }
+
+    pub fn width(&self) -> i64 {
+        self.max_x - self.min_x + 1
+    }
+
+    pub fn height(&self) -> i64 {
+        self.max_y - self.min_y + 1
+    }
+}
+
+impl fmt::Display for Bounds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "({},{})-({},{}) {}x{}",
+            self.min_x,
+            self.min_y,
+            self.max_x,
+            self.max_y,
+            self.width(),
+            self.height()
+        )
+    }
+}
+
+/// Statistics of the current generation, kept up to date as the universe changes.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default)]
+pub struct Stats {
+    pub population: usize,
+    /// Cells born in the last update.
+    pub births: usize,
+    /// Cells which died in the last update.
+    pub deaths: usize,
+    /// Bounding box of the live cells.
+    pub bounds: Option<Bounds>,
+}
+
+impl Stats {
+    fn include(&mut self, k: &GridCoord) {
+        if let GridCoord::Valid(x, y) = *k {
+            match &mut self.bounds {
+                Some(b) => b.include(x, y),
+                None => {
+                    self.bounds = Some(Bounds {
+                        min_x: x,
+                        min_y: y,
+                        max_x: x,
+                        max_y: y,
+                    })
+                }
+            }
+        }
+    }
+
+    // A cell was made alive between updates.
+    fn added(&mut self, k: &GridCoord) {
+        self.population += 1;
+        self.include(k);
+    }
+
+    // A cell was made dead between updates, returning true if the bounds must be recalculated.
+    fn removed(&mut self, k: &GridCoord) -> bool {
+        self.population -= 1;
+        match (*k, self.bounds) {
+            (GridCoord::Valid(x, y), Some(b)) => {
+                x == b.min_x || x == b.max_x || y == b.min_y || y == b.max_y
+            }
+            _ => false,
+        }
+    }
+}
+
+// Universe API -----------------------------------
+
+/// Common API over the universe implementations, so the backend can be chosen at runtime.
+pub trait Life {
+    /// Make the cell alive.
+    fn set(&mut self, k: GridCoord);
+    /// Make the cell dead.
+    fn unset(&mut self, k: GridCoord);
+    fn is_alive(&self, k: &GridCoord) -> bool;
+    fn live_cells(&self) -> Vec<GridCoord>;
+
+    /// Advance one generation, returning the statistics of the new generation.
+    fn update(&mut self) -> Stats;
+
+    /// Advance a number of generations, returning the statistics of the last. Backends able to
+    /// skip ahead faster than a generation at a time, e.g. HashLife, override this.
+    fn advance(&mut self, generations: usize) -> Stats {
+        for _ in 0..generations {
+            self.update();
+        }
+        self.stats()
+    }
+
+    fn generation(&self) -> usize;
+    fn rule(&self) -> Rule;
+
+    /// Statistics of the current generation, without a pass over the cells.
+    fn stats(&self) -> Stats;
+
+    /// Bounding box of the live cells.
+    fn bounds(&self) -> Option<Bounds> {
+        self.stats().bounds
+    }
+
+    /// Live cells within the bounds, without visiting those elsewhere.
+    fn cells_in(&self, bounds: &Bounds) -> Vec<GridCoord>;
+
+    /// Live cell counts of the `size` blocks of 2^shift cells starting at block `origin`, row by
+    /// row, in time proportional to the number of blocks rather than the population.
+    fn block_counts(&mut self, shift: u32, origin: (i64, i64), size: (usize, usize)) -> Vec<u32>;
+
+    /// State of the cell: 0 dead, 1 alive or from 2 dying in a Generations rule.
+    fn state(&self, k: &GridCoord) -> u8 {
+        self.is_alive(k) as u8
+    }
+
+    /// Set the state of the cell, dying states the rule doesn't have making it dead.
+    fn set_state(&mut self, k: GridCoord, state: u8) {
+        if state == 1 {
+            self.set(k);
+        } else {
+            self.unset(k);
+        }
+    }
+
+    /// Dying cells with their states.
+    fn dying_cells(&self) -> Vec<(GridCoord, u8)> {
+        vec![]
+    }
+
+    /// Dying cells within the bounds with their states.
+    fn dying_in(&self, _bounds: &Bounds) -> Vec<(GridCoord, u8)> {
+        vec![]
+    }
+
+    /// Live and dying cells with their states.
+    fn cell_states(&self) -> Vec<(GridCoord, u8)> {
+        let mut cells: Vec<(GridCoord, u8)> =
+            self.live_cells().into_iter().map(|c| (c, 1)).collect();
+        cells.extend(self.dying_cells());
+        cells
+    }
+
+    /// Live and dying cells within the bounds with their states.
+    fn states_in(&self, bounds: &Bounds) -> Vec<(GridCoord, u8)> {
+        let mut cells: Vec<(GridCoord, u8)> =
+            self.cells_in(bounds).into_iter().map(|c| (c, 1)).collect();
+        cells.extend(self.dying_in(bounds));
+        cells
+    }
+}
+
+/// The available universe implementations.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Backend {
+    #[default]
+    Generations,
+    Naive,
+}
+
+impl Backend {
+    /// Create an empty universe with this implementation.
+    pub fn create(&self, rule: Rule) -> Box<dyn Life> {
+        match self {
+            Backend::Generations => Box::new(Universe::with_rule(rule)),
+            Backend::Naive => Box::new(UniverseOld::with_rule(rule)),
+        }
+    }
+}
+
+impl FromStr for Backend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "generations" => Ok(Backend::Generations),
+            "naive" => Ok(Backend::Naive),
+            _ => Err(anyhow!("Unknown backend: {s}")),
+        }
+    }
+}
+
+// Spatial index ----------------------------------
+
+// Tiles are 2^TILE_SHIFT cells square.
+const TILE_SHIFT: u32 = 5;
+
+/// Live cells grouped into square tiles, to find those in a rectangle without visiting the rest.
+#[derive(Debug, Default)]
+pub struct TileIndex {
+    tiles: HashMap<(i64, i64), HashSet<(i64, i64)>>,
+    // Occupied tiles by column and by row, to find the edge tiles.
+    columns: BTreeMap<i64, BTreeSet<i64>>,
+    rows: BTreeMap<i64, BTreeSet<i64>>,
+    // Counts in blocks of tiles and larger, once drawn zoomed out that far.
+    density: Option<Density>,
+}
+
+impl TileIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn tile(x: i64, y: i64) -> (i64, i64) {
+        (x >> TILE_SHIFT, y >> TILE_SHIFT)
+    }
+
+    pub fn insert(&mut self, k: &GridCoord) {
+        if let GridCoord::Valid(x, y) = *k {
+            let (tx, ty) = Self::tile(x, y);
+            let cells = self.tiles.entry((tx, ty)).or_insert_with(|| {
+                self.columns.entry(tx).or_default().insert(ty);
+                self.rows.entry(ty).or_default().insert(tx);
+                HashSet::new()
+            });
+            if cells.insert((x, y)) {
+                if let Some(density) = &mut self.density {
+                    density.add(x, y);
+                }
+            }
+        }
+    }
+
+    pub fn remove(&mut self, k: &GridCoord) {
+        if let GridCoord::Valid(x, y) = *k {
+            let tile = Self::tile(x, y);
+            if let Some(cells) = self.tiles.get_mut(&tile) {
+                if cells.remove(&(x, y)) {
+                    if let Some(density) = &mut self.density {
+                        density.remove(x, y);
+                    }
+                }
+                if cells.is_empty() {
+                    self.tiles.remove(&tile);
+                    Self::vacate(&mut self.columns, tile.0, tile.1);
+                    Self::vacate(&mut self.rows, tile.1, tile.0);
+                }
+            }
+        }
+    }
+
+    fn vacate(lines: &mut BTreeMap<i64, BTreeSet<i64>>, line: i64, tile: i64) {
+        if let Some(tiles) = lines.get_mut(&line) {
+            tiles.remove(&tile);
+            if tiles.is_empty() {
+                lines.remove(&line);
+            }
+        }
+    }
+
+    /// Bounding box of the cells, visiting only those in the edge tiles.
+    pub fn bounds(&self) -> Option<Bounds> {
+        let (&left, left_tiles) = self.columns.first_key_value()?;
+        let (&right, right_tiles) = self.columns.last_key_value()?;
+        let (&top, top_tiles) = self.rows.first_key_value()?;
+        let (&bottom, bottom_tiles) = self.rows.last_key_value()?;
+
+        Some(Bounds {
+            min_x: self
+                .cells_of(left_tiles.iter().map(|&ty| (left, ty)))
+                .map(|c| c.0)
+                .min()?,
+            min_y: self
+                .cells_of(top_tiles.iter().map(|&tx| (tx, top)))
+                .map(|c| c.1)
+                .min()?,
+            max_x: self
+                .cells_of(right_tiles.iter().map(|&ty| (right, ty)))
+                .map(|c| c.0)
+                .max()?,
+            max_y: self
+                .cells_of(bottom_tiles.iter().map(|&tx| (tx, bottom)))
+                .map(|c| c.1)
+                .max()?,
+        })
+    }
+
+    fn cells_of<'a>(
+        &'a self,
+        tiles: impl Iterator<Item = (i64, i64)> + 'a,
+    ) -> impl Iterator<Item = (i64, i64)> + 'a {
+        tiles.flat_map(|t| self.tiles[&t].iter().copied())
+    }
+
+    /// Live cell counts of the `size` blocks of 2^shift cells starting at block `origin`, row by
+    /// row. Blocks smaller than a tile are counted from the cells in the area, larger ones from
+    /// tile counts kept as cells come and go.
+    pub fn counts(&mut self, shift: u32, origin: (i64, i64), size: (usize, usize)) -> Vec<u32> {
+        let (w, h) = size;
+        if shift >= TILE_SHIFT {
+            let tiles = &self.tiles;
+            return self
+                .density
+                .get_or_insert_with(|| {
+                    let blocks = tiles.iter().map(|(&t, c)| (t, c.len() as u32)).collect();
+                    Density::new(TILE_SHIFT, blocks)
+                })
+                .counts(shift, origin, size);
+        }
+
+        let mut counts = vec![0; w * h];
+        if w == 0 || h == 0 {
+            return counts;
+        }
+        let area = Bounds {
+            min_x: origin.0 << shift,
+            min_y: origin.1 << shift,
+            max_x: ((origin.0 + w as i64) << shift) - 1,
+            max_y: ((origin.1 + h as i64) << shift) - 1,
+        };
+        for c in self.query(&area) {
+            if let GridCoord::Valid(x, y) = c {
+                let (dx, dy) = ((x >> shift) - origin.0, (y >> shift) - origin.1);
+                counts[dy as usize * w + dx as usize] += 1;
+            }
+        }
+
+        counts
+    }
+
+    /// Live cells within the bounds.
+    pub fn query(&self, bounds: &Bounds) -> Vec<GridCoord> {
+        let (tx0, ty0) = Self::tile(bounds.min_x, bounds.min_y);
+        let (tx1, ty1) = Self::tile(bounds.max_x, bounds.max_y);
+
+        let mut cells = vec![];
+        let mut visit = |tile: &HashSet<(i64, i64)>| {
+            cells.extend(
+                tile.iter()
+                    .filter(|&&(x, y)| bounds.contains(x, y))
+                    .map(|&(x, y)| GridCoord::Valid(x, y)),
+            );
+        };
+
+        // Visit whichever is fewer: the tiles in the bounds or the occupied tiles.
+        let area = (tx1 - tx0 + 1).saturating_mul(ty1 - ty0 + 1);
+        if area <= self.tiles.len() as i64 {
+            for ty in ty0..=ty1 {
+                for tx in tx0..=tx1 {
+                    if let Some(tile) = self.tiles.get(&(tx, ty)) {
+                        visit(tile);
+                    }
+                }
+            }
+        } else {
+            for (&(tx, ty), tile) in &self.tiles {
+                if tx >= tx0 && tx <= tx1 && ty >= ty0 && ty <= ty1 {
+                    visit(tile);
+                }
+            }
+        }
+
+        cells
+    }
+}
+
 // Naive universe ---------------------------------
 
+/// Sparse grid for the naive universe, mapping cells to neighbour tallies.
 pub struct SparseGridOld {
     elements: HashMap<GridCoord, usize>,
+    stats: Stats,
+    index: TileIndex,
+}
+
+impl Default for SparseGridOld {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[allow(unused)]
@@ -47,15 +469,25 @@ impl SparseGridOld {
     pub fn new() -> Self {
         SparseGridOld {
             elements: HashMap::new(),
+            stats: Stats::default(),
+            index: TileIndex::new(),
         }
     }
 
     pub fn set(&mut self, k: GridCoord, v: usize) {
-        self.elements.insert(k, v);
+        if self.elements.insert(k, v).is_none() {
+            self.stats.added(&k);
+            self.index.insert(&k);
+        }
     }
 
     pub fn unset(&mut self, k: GridCoord) {
-        self.elements.remove(&k);
+        if self.elements.remove(&k).is_some() {
+            self.index.remove(&k);
+            if self.stats.removed(&k) {
+                self.stats.bounds = self.index.bounds();
+            }
+        }
     }
 
     pub fn get(&self, k: &GridCoord) -> Option<usize> {
@@ -80,14 +512,10 @@ impl SparseGridOld {
         elements
     }
 
+    // Tallies aren't live cells, so bypass the stats.
     fn tally(&mut self, cells: &[GridCoord]) {
         for c in cells {
-            let count = match self.elements.get(c) {
-                Some(&v) => v + 1,
-                None => 1,
-            };
-
-            self.set(*c, count);
+            *self.elements.entry(*c).or_insert(0) += 1;
         }
     }
 
@@ -102,55 +530,219 @@ impl SparseGridOld {
         self.elements.keys().len()
     }
 
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    pub fn cells_in(&self, bounds: &Bounds) -> Vec<GridCoord> {
+        self.index.query(bounds)
+    }
+
+    pub fn block_counts(
+        &mut self,
+        shift: u32,
+        origin: (i64, i64),
+        size: (usize, usize),
+    ) -> Vec<u32> {
+        self.index.counts(shift, origin, size)
+    }
+
     pub fn is_empty(&self) -> bool {
         self.elements.is_empty()
     }
 }
 
+/// Naive universe, rebuilding the grid every generation.
 pub struct UniverseOld {
     pub grid: SparseGridOld,
+    // States of the dying cells, in Generations rules.
+    pub dying: HashMap<GridCoord, u8>,
     pub generation: usize,
+    pub rule: Rule,
+}
+
+impl Default for UniverseOld {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[allow(unused)]
 impl UniverseOld {
     pub fn new() -> UniverseOld {
+        Self::with_rule(Rule::default())
+    }
+
+    pub fn with_rule(rule: Rule) -> UniverseOld {
         UniverseOld {
             grid: SparseGridOld::new(),
+            dying: HashMap::new(),
             generation: 0,
+            rule,
         }
     }
 
-    pub fn update(&mut self) -> usize {
+    pub fn update(&mut self) -> Stats {
         self.generation += 1;
-        let mut cell_count: usize = 0;
         let mut next = SparseGridOld::new();
         for c in self.grid.elements() {
             next.tally(&c.expand());
-            cell_count += 1;
         }
 
-        next.retain(|gc, v| *v == 3 || (*v == 4 && self.grid.is_alive(gc)));
+        // Dying cells carry on dying whatever their neighbours.
+        let mut dying: HashMap<GridCoord, u8> = self
+            .dying
+            .iter()
+            .map(|(&c, &state)| (c, self.rule.next(state, 0)))
+            .filter(|&(_, state)| state != 0)
+            .collect();
+
+        // The index carries over, changed only by the births and deaths.
+        let mut stats = Stats::default();
+        let mut index = std::mem::take(&mut self.grid.index);
+        let mut survivors = 0;
+        next.retain(|gc, v| {
+            let was_alive = self.grid.is_alive(gc);
+            if !was_alive && self.dying.contains_key(gc) {
+                return false;
+            }
+            // Live cells tally themselves.
+            let state = self.rule.next(was_alive as u8, *v - was_alive as usize);
+            let alive = state == 1;
+            if state > 1 {
+                dying.insert(*gc, state);
+            }
+            if alive {
+                stats.population += 1;
+                stats.include(gc);
+                if was_alive {
+                    survivors += 1;
+                } else {
+                    index.insert(gc);
+                    stats.births += 1;
+                }
+            } else if was_alive {
+                index.remove(gc);
+            }
+
+            alive
+        });
+        stats.deaths = self.grid.len() - survivors;
+        next.stats = stats;
+        next.index = index;
 
         self.grid = next;
+        self.dying = dying;
 
-        cell_count
+        stats
+    }
+}
+
+impl Life for UniverseOld {
+    fn set(&mut self, k: GridCoord) {
+        self.dying.remove(&k);
+        self.grid.set(k, 1);
+    }
+
+    fn unset(&mut self, k: GridCoord) {
+        self.dying.remove(&k);
+        self.grid.unset(k);
+    }
+
+    fn is_alive(&self, k: &GridCoord) -> bool {
+        self.grid.is_alive(k)
+    }
+
+    fn live_cells(&self) -> Vec<GridCoord> {
+        self.grid.elements()
+    }
+
+    fn update(&mut self) -> Stats {
+        UniverseOld::update(self)
+    }
+
+    fn generation(&self) -> usize {
+        self.generation
+    }
+
+    fn rule(&self) -> Rule {
+        self.rule
+    }
+
+    fn stats(&self) -> Stats {
+        self.grid.stats()
+    }
+
+    fn cells_in(&self, bounds: &Bounds) -> Vec<GridCoord> {
+        self.grid.cells_in(bounds)
+    }
+
+    fn block_counts(&mut self, shift: u32, origin: (i64, i64), size: (usize, usize)) -> Vec<u32> {
+        self.grid.block_counts(shift, origin, size)
+    }
+
+    fn state(&self, k: &GridCoord) -> u8 {
+        match self.dying.get(k) {
+            Some(&state) => state,
+            None => self.grid.is_alive(k) as u8,
+        }
+    }
+
+    fn set_state(&mut self, k: GridCoord, state: u8) {
+        match state {
+            1 => self.set(k),
+            state if state > 1 && state < self.rule.states() => {
+                self.grid.unset(k);
+                self.dying.insert(k, state);
+            }
+            _ => self.unset(k),
+        }
+    }
+
+    fn dying_cells(&self) -> Vec<(GridCoord, u8)> {
+        self.dying.iter().map(|(&c, &state)| (c, state)).collect()
+    }
+
+    fn dying_in(&self, bounds: &Bounds) -> Vec<(GridCoord, u8)> {
+        self.dying
+            .iter()
+            .filter(|&(c, _)| matches!(*c, GridCoord::Valid(x, y) if bounds.contains(x, y)))
+            .map(|(&c, &state)| (c, state))
+            .collect()
     }
 }
 
 // Generational universe -----------------------------
 
+/// A cell in the generational grid: alive, dying or a dead neighbour of a live cell.
 #[derive(Debug)]
 pub struct Cell {
-    pub is_alive: bool,
+    /// 0 dead, 1 alive or from 2 dying in a Generations rule.
+    pub state: u8,
     pub generation: usize,
     pub tally: usize,
 }
 
+impl Cell {
+    pub fn is_alive(&self) -> bool {
+        self.state == 1
+    }
+}
+
+/// Sparse grid updated in place, tagging tallies with the generation they belong to.
 #[derive(Debug)]
 pub struct SparseGridGenerations {
     pub elements: HashMap<GridCoord, Cell>,
     pub generation: usize,
+    stats: Stats,
+    index: TileIndex,
+    dying: TileIndex,
+}
+
+impl Default for SparseGridGenerations {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[allow(unused)]
@@ -159,36 +751,111 @@ impl SparseGridGenerations {
         SparseGridGenerations {
             elements: HashMap::new(),
             generation: 0,
+            stats: Stats::default(),
+            index: TileIndex::new(),
+            dying: TileIndex::new(),
         }
     }
 
     pub fn set(&mut self, k: GridCoord) {
-        self.elements.insert(
+        self.set_state(k, 1);
+    }
+
+    pub fn set_state(&mut self, k: GridCoord, state: u8) {
+        if state == 0 {
+            self.unset(k);
+            return;
+        }
+
+        let old = self.elements.insert(
             k,
             Cell {
-                is_alive: true,
+                state,
                 generation: self.generation,
                 tally: 0,
             },
         );
+        self.changed(&k, old.map_or(0, |c| c.state), state);
     }
 
     pub fn unset(&mut self, k: GridCoord) {
         // TODO Do we need to optimise this too to remember recently removed cells?
-        self.elements.remove(&k);
+        if let Some(removed) = self.elements.remove(&k) {
+            self.changed(&k, removed.state, 0);
+        }
     }
 
-    pub fn is_alive(&self, k: &GridCoord) -> bool {
-        match self.elements.get(k) {
-            Some(v) => v.is_alive,
-            None => false,
+    // Keep the stats and indexes up to date as a cell is edited between updates.
+    fn changed(&mut self, k: &GridCoord, old: u8, new: u8) {
+        if old == new {
+            return;
+        }
+
+        match old {
+            0 => {}
+            1 => {
+                self.index.remove(k);
+                if self.stats.removed(k) {
+                    self.stats.bounds = self.index.bounds();
+                }
+            }
+            _ => self.dying.remove(k),
+        }
+        match new {
+            0 => {}
+            1 => {
+                self.stats.added(k);
+                self.index.insert(k);
+            }
+            _ => self.dying.insert(k),
         }
     }
 
+    pub fn state(&self, k: &GridCoord) -> u8 {
+        self.elements.get(k).map_or(0, |c| c.state)
+    }
+
+    pub fn dying_cells(&self) -> Vec<(GridCoord, u8)> {
+        self.elements
+            .iter()
+            .filter(|&(_, v)| v.state > 1)
+            .map(|(k, v)| (*k, v.state))
+            .collect()
+    }
+
+    pub fn dying_in(&self, bounds: &Bounds) -> Vec<(GridCoord, u8)> {
+        self.dying
+            .query(bounds)
+            .into_iter()
+            .map(|k| (k, self.state(&k)))
+            .collect()
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    pub fn cells_in(&self, bounds: &Bounds) -> Vec<GridCoord> {
+        self.index.query(bounds)
+    }
+
+    pub fn block_counts(
+        &mut self,
+        shift: u32,
+        origin: (i64, i64),
+        size: (usize, usize),
+    ) -> Vec<u32> {
+        self.index.counts(shift, origin, size)
+    }
+
+    pub fn is_alive(&self, k: &GridCoord) -> bool {
+        self.elements.get(k).is_some_and(|v| v.is_alive())
+    }
+
     pub fn live_cells(&self) -> Vec<GridCoord> {
         self.elements
             .iter()
-            .filter(|&(k, v)| v.is_alive)
+            .filter(|&(k, v)| v.is_alive())
             .map(|(k, _)| *k)
             .collect()
     }
@@ -196,7 +863,7 @@ impl SparseGridGenerations {
     pub fn live_cells_ref(&self) -> Vec<&GridCoord> {
         self.elements
             .iter()
-            .filter(|&(k, v)| v.is_alive)
+            .filter(|&(k, v)| v.is_alive())
             .map(|(k, _)| k)
             .collect()
     }
@@ -217,7 +884,7 @@ impl SparseGridGenerations {
                     self.elements.insert(
                         *c,
                         Cell {
-                            is_alive: false,
+                            state: 0,
                             generation,
                             tally: 1,
                         },
@@ -228,57 +895,155 @@ impl SparseGridGenerations {
     }
 
     // Complete the generation...
-    fn finalise(&mut self, generation: usize) {
+    fn finalise(&mut self, generation: usize, rule: &Rule) {
+        let mut stats = Stats::default();
+        let (index, dying) = (&mut self.index, &mut self.dying);
         self.elements.retain(|k, v| {
-            // Firstly... adjust the cells to the correct life
-            // if v.generation < generation {
-            //     // This cell has no neighbours... so will die
-            //     v.is_alive = false;
-            // } else {
-            if v.generation == generation {
-                // This cell has some neighbours, so might live.
-                let t = v.tally;
-                v.is_alive = t == 3 || (t == 4 && v.is_alive);
-            }
+            let was = v.state;
+
+            // Cells without a tally this generation have no live neighbours, and live cells
+            // tally themselves. Dying cells carry on dying whatever their neighbours.
+            let neighbours = match (v.generation == generation, was) {
+                (false, _) => 0,
+                (true, 1) => v.tally - 1,
+                (true, _) => v.tally,
+            };
+            v.state = rule.next(was, neighbours);
 
             //println!("Finalise: {:?} => {:?}", k, v);
 
-            // Discard cells which had no neighbours
-            v.generation == generation
+            match (was, v.state) {
+                (1, 1) => {}
+                (_, 1) => {
+                    stats.births += 1;
+                    index.insert(k);
+                }
+                (1, state) => {
+                    stats.deaths += 1;
+                    index.remove(k);
+                    if state > 1 {
+                        dying.insert(k);
+                    }
+                }
+                (was, 0) if was > 1 => dying.remove(k),
+                _ => {}
+            }
+            if v.state == 1 {
+                stats.population += 1;
+                stats.include(k);
+            }
+
+            // Discard cells which had no neighbours, unless dying
+            v.generation == generation || v.state > 1
         });
+        self.stats = stats;
     }
 }
 
+/// Generational universe, the default implementation.
 pub struct Universe {
     pub grid: SparseGridGenerations,
     pub generation: usize,
+    pub rule: Rule,
+}
+
+impl Default for Universe {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[allow(unused)]
 impl Universe {
     pub fn new() -> Self {
+        Self::with_rule(Rule::default())
+    }
+
+    pub fn with_rule(rule: Rule) -> Self {
         Universe {
             grid: SparseGridGenerations::new(),
             generation: 0,
+            rule,
         }
     }
 
-    pub fn update(&mut self) -> usize {
+    pub fn update(&mut self) -> Stats {
         self.generation += 1;
-        let mut cell_count: usize = 0;
         for c in self.grid.live_cells() {
             //println!("Cell: {:?}", c);
             self.grid.tally(self.generation, &c.expand());
-            cell_count += 1;
         }
 
-        self.grid.finalise(self.generation);
+        self.grid.finalise(self.generation, &self.rule);
 
-        cell_count
+        self.grid.stats()
+    }
+}
+
+impl Life for Universe {
+    fn set(&mut self, k: GridCoord) {
+        self.grid.set(k);
+    }
+
+    fn unset(&mut self, k: GridCoord) {
+        self.grid.unset(k);
+    }
+
+    fn is_alive(&self, k: &GridCoord) -> bool {
+        self.grid.is_alive(k)
+    }
+
+    fn live_cells(&self) -> Vec<GridCoord> {
+        self.grid.live_cells()
+    }
+
+    fn update(&mut self) -> Stats {
+        Universe::update(self)
+    }
+
+    fn generation(&self) -> usize {
+        self.generation
+    }
+
+    fn rule(&self) -> Rule {
+        self.rule
+    }
+
+    fn stats(&self) -> Stats {
+        self.grid.stats()
+    }
+
+    fn cells_in(&self, bounds: &Bounds) -> Vec<GridCoord> {
+        self.grid.cells_in(bounds)
+    }
+
+    fn block_counts(&mut self, shift: u32, origin: (i64, i64), size: (usize, usize)) -> Vec<u32> {
+        self.grid.block_counts(shift, origin, size)
+    }
+
+    fn state(&self, k: &GridCoord) -> u8 {
+        self.grid.state(k)
+    }
+
+    fn set_state(&mut self, k: GridCoord, state: u8) {
+        if state < self.rule.states() {
+            self.grid.set_state(k, state);
+        } else {
+            self.grid.unset(k);
+        }
+    }
+
+    fn dying_cells(&self) -> Vec<(GridCoord, u8)> {
+        self.grid.dying_cells()
+    }
+
+    fn dying_in(&self, bounds: &Bounds) -> Vec<(GridCoord, u8)> {
+        self.grid.dying_in(bounds)
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use std::assert_eq;
 
@@ -367,7 +1132,7 @@ mod tests {
 
         println!("Grid: {:?}", universe.grid);
 
-        let c = universe.update();
+        let c = universe.update().population;
 
         println!("Update: {:?}", universe.grid);
 
@@ -379,4 +1144,271 @@ mod tests {
         assert_eq!(universe.grid.is_alive(&K4), true);
         assert_eq!(universe.grid.is_alive(&K5), true);
     }
+
+    #[test]
+    fn test_blinker_stats() {
+        let mut universe = Universe::new();
+
+        universe.grid.set(K1);
+        universe.grid.set(K2);
+        universe.grid.set(K3);
+
+        let stats = universe.update();
+
+        assert_eq!((stats.population, stats.births, stats.deaths), (3, 2, 2));
+    }
+
+    #[test]
+    fn test_rule_generations() {
+        let mut universe = Universe::with_rule("B1/S".parse().unwrap());
+
+        universe.grid.set(K2);
+
+        universe.update();
+
+        assert!(!universe.grid.is_alive(&K2));
+        assert!(universe.grid.is_alive(&K1));
+        assert!(universe.grid.is_alive(&K3));
+        assert!(universe.grid.is_alive(&K4));
+        assert!(universe.grid.is_alive(&K5));
+        assert_eq!(universe.grid.live_cells().len(), 8);
+    }
+
+    #[test]
+    fn test_backends_agree() {
+        for backend in [Backend::Generations, Backend::Naive] {
+            let mut universe = backend.create(Rule::default());
+
+            universe.set(K1);
+            universe.set(K2);
+            universe.set(K3);
+
+            let stats = universe.update();
+            assert_eq!(
+                (stats.population, stats.births, stats.deaths),
+                (3, 2, 2),
+                "{:?}",
+                backend
+            );
+            assert_eq!(stats.bounds, Bounds::of(&[K4, K5]));
+            assert_eq!(universe.generation(), 1);
+
+            let mut cells = universe.live_cells();
+            cells.sort();
+            assert_eq!(cells, vec![K4, K2, K5], "{:?}", backend);
+        }
+    }
+
+    #[test]
+    fn test_generations_rule() {
+        // Brian's Brain: a pair of live cells with a dying cell behind them moves along.
+        let rule: Rule = "B2/S/C3".parse().unwrap();
+        for backend in [Backend::Generations, Backend::Naive] {
+            let mut universe = backend.create(rule);
+            for y in 0..2 {
+                universe.set_state(GridCoord::Valid(0, y), 2);
+                universe.set(GridCoord::Valid(1, y));
+            }
+            universe.set_state(GridCoord::Valid(5, 5), 3);
+            assert_eq!(universe.state(&GridCoord::Valid(5, 5)), 0, "{:?}", backend);
+
+            let stats = universe.update();
+            assert_eq!(
+                (stats.population, stats.births, stats.deaths),
+                (2, 2, 2),
+                "{:?}",
+                backend
+            );
+            let mut cells = universe.cell_states();
+            cells.sort();
+            let expected = vec![
+                (GridCoord::Valid(1, 0), 2),
+                (GridCoord::Valid(1, 1), 2),
+                (GridCoord::Valid(2, 0), 1),
+                (GridCoord::Valid(2, 1), 1),
+            ];
+            assert_eq!(cells, expected, "{:?}", backend);
+
+            let area = Bounds {
+                min_x: 0,
+                min_y: 0,
+                max_x: 1,
+                max_y: 0,
+            };
+            assert_eq!(
+                universe.states_in(&area),
+                vec![(GridCoord::Valid(1, 0), 2)],
+                "{:?}",
+                backend
+            );
+
+            // Setting a dying cell alive, or dead.
+            universe.set(GridCoord::Valid(1, 0));
+            universe.unset(GridCoord::Valid(1, 1));
+            assert_eq!(universe.dying_cells(), vec![], "{:?}", backend);
+            assert_eq!(universe.stats().population, 3, "{:?}", backend);
+        }
+    }
+
+    #[test]
+    fn test_advance() {
+        let glider = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+        for backend in [Backend::Generations, Backend::Naive] {
+            let mut universe = backend.create(Rule::default());
+            for (x, y) in glider {
+                universe.set(GridCoord::Valid(x, y));
+            }
+
+            // After 4 generations the glider has moved one cell diagonally.
+            let stats = universe.advance(4);
+            assert_eq!(universe.generation(), 4);
+            assert_eq!(stats.population, 5, "{:?}", backend);
+            let mut cells = universe.live_cells();
+            cells.sort();
+            let mut moved: Vec<GridCoord> = glider
+                .iter()
+                .map(|&(x, y)| GridCoord::Valid(x + 1, y + 1))
+                .collect();
+            moved.sort();
+            assert_eq!(cells, moved, "{:?}", backend);
+        }
+    }
+
+    #[test]
+    fn test_stats_edits() {
+        for backend in [Backend::Generations, Backend::Naive] {
+            let mut universe = backend.create(Rule::default());
+
+            universe.set(K1);
+            universe.set(K3);
+            universe.set(K3);
+            assert_eq!(universe.stats().population, 2, "{:?}", backend);
+            assert_eq!(universe.bounds(), Bounds::of(&[K1, K3]));
+
+            // Removing an edge cell shrinks the bounds.
+            universe.unset(K3);
+            universe.unset(K2);
+            assert_eq!(universe.stats().population, 1, "{:?}", backend);
+            assert_eq!(universe.bounds(), Bounds::of(&[K1]));
+
+            universe.unset(K1);
+            assert_eq!(universe.stats(), Stats::default(), "{:?}", backend);
+        }
+    }
+
+    #[test]
+    fn test_cells_in() {
+        let area = Bounds {
+            min_x: -40,
+            min_y: -1,
+            max_x: 0,
+            max_y: 1,
+        };
+        for backend in [Backend::Generations, Backend::Naive] {
+            let mut universe = backend.create(Rule::default());
+
+            // Blinkers either side of a tile boundary, and one far away.
+            for x in [-34, -30, 100] {
+                for y in 0..3 {
+                    universe.set(GridCoord::Valid(x, y));
+                }
+            }
+            universe.unset(GridCoord::Valid(100, 0));
+
+            let mut found = universe.cells_in(&area);
+            found.sort();
+            assert_eq!(found.len(), 4, "{:?}", backend);
+
+            // After an update the blinkers are horizontal.
+            universe.update();
+            let mut found = universe.cells_in(&area);
+            found.sort();
+            let expected: Vec<GridCoord> = (-35..=-33)
+                .chain(-31..=-29)
+                .map(|x| GridCoord::Valid(x, 1))
+                .collect();
+            assert_eq!(found, expected, "{:?}", backend);
+        }
+    }
+
+    #[test]
+    fn test_block_counts() {
+        for backend in [Backend::Generations, Backend::Naive] {
+            let mut universe = backend.create(Rule::default());
+            // Cells scattered across tiles and negative coordinates.
+            for i in 0..200i64 {
+                universe.set(GridCoord::Valid((i * 37) % 150 - 75, (i * 53) % 120 - 60));
+            }
+
+            for generation in 0..4 {
+                for (shift, origin, size) in [
+                    (0, (-80, -70), (160, 140)),
+                    (2, (-3, -20), (30, 30)),
+                    (5, (-3, -2), (5, 4)),
+                    (7, (-1, -1), (2, 2)),
+                ] {
+                    let mut expected = vec![0; size.0 * size.1];
+                    for c in universe.live_cells() {
+                        if let GridCoord::Valid(x, y) = c {
+                            let (dx, dy) = ((x >> shift) - origin.0, (y >> shift) - origin.1);
+                            if dx >= 0
+                                && dy >= 0
+                                && (dx as usize) < size.0
+                                && (dy as usize) < size.1
+                            {
+                                expected[dy as usize * size.0 + dx as usize] += 1;
+                            }
+                        }
+                    }
+                    assert_eq!(
+                        universe.block_counts(shift, origin, size),
+                        expected,
+                        "{:?} generation {} shift {}",
+                        backend,
+                        generation,
+                        shift
+                    );
+                }
+
+                universe.update();
+                universe.unset(GridCoord::Valid(-75, -60));
+                universe.set(GridCoord::Valid(100, 100));
+            }
+        }
+    }
+
+    #[test]
+    fn test_tile_bounds() {
+        let mut index = TileIndex::new();
+        assert_eq!(index.bounds(), None);
+
+        let cells = [
+            GridCoord::Valid(-70, 5),
+            GridCoord::Valid(3, -40),
+            GridCoord::Valid(31, 100),
+            GridCoord::Valid(-65, 7),
+        ];
+        for c in &cells {
+            index.insert(c);
+        }
+        assert_eq!(index.bounds(), Bounds::of(&cells));
+
+        index.remove(&cells[0]);
+        index.remove(&cells[2]);
+        assert_eq!(index.bounds(), Bounds::of(&[cells[1], cells[3]]));
+
+        index.remove(&cells[1]);
+        index.remove(&cells[3]);
+        assert_eq!(index.bounds(), None);
+    }
+
+    #[test]
+    fn test_bounds() {
+        assert_eq!(Bounds::of(&[]), None);
+
+        let b = Bounds::of(&[K1, K3, K4]).expect("Missing bounds");
+        assert_eq!((b.min_x, b.min_y, b.max_x, b.max_y), (-1, 0, 0, 2));
+        assert_eq!((b.width(), b.height()), (2, 3));
+        assert!(b.contains(-1, 2) && !b.contains(1, 0));
+    }
 }
@@ -0,0 +1,305 @@
+//! Running a universe without graphics.
+
+use anyhow::{anyhow, Result};
+use log::*;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::grid::Life;
+use crate::objects::{analyse, Separation};
+use crate::period::{PeriodDetector, Periodicity};
+use crate::record::{Recorder, Recording};
+use crate::render::{render, Style};
+use crate::rle::save_rle_states;
+use crate::stats::History;
+
+/// Condition, checked after every generation, to end a headless run.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum StopCondition {
+    Empty,
+    PopulationAbove(usize),
+    PopulationBelow(usize),
+    Periodic,
+}
+
+impl StopCondition {
+    fn is_met(&self, universe: &dyn Life, periodicity: Option<Periodicity>) -> bool {
+        let population = universe.stats().population;
+        match *self {
+            StopCondition::Empty => population == 0,
+            StopCondition::PopulationAbove(n) => population > n,
+            StopCondition::PopulationBelow(n) => population < n,
+            StopCondition::Periodic => periodicity.is_some(),
+        }
+    }
+}
+
+impl FromStr for StopCondition {
+    type Err = anyhow::Error;
+
+    // Accepts: empty, periodic, population>N, population<N.
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if s == "empty" {
+            Ok(StopCondition::Empty)
+        } else if s == "periodic" {
+            Ok(StopCondition::Periodic)
+        } else if let Some(n) = s.strip_prefix("population>") {
+            Ok(StopCondition::PopulationAbove(n.trim().parse()?))
+        } else if let Some(n) = s.strip_prefix("population<") {
+            Ok(StopCondition::PopulationBelow(n.trim().parse()?))
+        } else {
+            Err(anyhow!("Unknown condition: {s}"))
+        }
+    }
+}
+
+/// Parameters for a headless run.
+#[derive(Debug, Clone)]
+pub struct Batch {
+    pub generations: Option<usize>,
+    pub until: Option<StopCondition>,
+    pub report_every: usize,
+    pub output: Option<String>,
+    // List the objects in the final pattern, separated so.
+    pub objects: Option<Separation>,
+    // CSV file for the statistics of every generation.
+    pub stats: Option<String>,
+    // PNG file for an image of the final pattern, drawn so.
+    pub image: Option<String>,
+    pub image_style: Style,
+    // Animation of the run.
+    pub record: Option<Recording>,
+}
+
+fn report(universe: &dyn Life, interval: Duration, generations: usize) {
+    let stats = universe.stats();
+    let bounds = match stats.bounds {
+        Some(b) => b.to_string(),
+        None => "-".to_owned(),
+    };
+    let secs = interval.as_secs_f64();
+    let rate = if secs > 0.0 {
+        generations as f64 / secs
+    } else {
+        0.0
+    };
+
+    println!(
+        "{:>12} {:>12} {:>36} {:>12.3} {:>12.1}",
+        universe.generation(),
+        stats.population,
+        bounds,
+        secs * 1000.0,
+        rate
+    );
+}
+
+/// Run the universe without any graphics, reporting progress to stdout.
+pub fn run(universe: &mut dyn Life, batch: &Batch) -> Result<()> {
+    if batch.generations.is_none() && batch.until.is_none() && batch.record.is_none() {
+        return Err(anyhow!(
+            "Headless mode requires --generations, --until or --record"
+        ));
+    }
+    // A pattern that never repeats would otherwise run forever.
+    if batch.until == Some(StopCondition::Periodic) && batch.generations.is_none() {
+        return Err(anyhow!("--until periodic requires --generations"));
+    }
+    if batch.report_every == 0 {
+        return Err(anyhow!("Report interval must be at least 1"));
+    }
+
+    println!(
+        "{:>12} {:>12} {:>36} {:>12} {:>12}",
+        "generation", "population", "bounds", "interval_ms", "gen_per_s"
+    );
+    report(universe, Duration::ZERO, 0);
+
+    // Periods are only looked for when they end the run.
+    let mut detector = (batch.until == Some(StopCondition::Periodic)).then(PeriodDetector::new);
+    let mut periodicity = detector.as_mut().and_then(|d| d.observe(universe));
+
+    let mut history = batch.stats.as_ref().map(|_| {
+        let mut history = History::new(usize::MAX);
+        history.reset(universe);
+        history
+    });
+
+    let mut recorder = batch.record.clone().map(Recorder::new).transpose()?;
+    if let Some(recorder) = &mut recorder {
+        recorder.observe(universe);
+    }
+
+    let start = Instant::now();
+    let mut interval_start = start;
+    let mut interval_generations = 0;
+    let reason = loop {
+        if let Some(limit) = batch.generations {
+            if universe.generation() >= limit {
+                break "generation limit reached".to_owned();
+            }
+        }
+        // Recording alone runs until the recording is complete.
+        if batch.generations.is_none()
+            && batch.until.is_none()
+            && recorder.as_ref().is_some_and(|r| r.is_complete())
+        {
+            break "recording complete".to_owned();
+        }
+
+        let update_start = Instant::now();
+        universe.update();
+        if let Some(history) = &mut history {
+            history.record(universe, update_start.elapsed());
+        }
+        interval_generations += 1;
+        if let Some(detector) = &mut detector {
+            periodicity = detector.observe(universe);
+        }
+        if let Some(recorder) = &mut recorder {
+            recorder.observe(universe);
+        }
+
+        let met = batch.until.filter(|c| c.is_met(universe, periodicity));
+
+        if met.is_some() || universe.generation().is_multiple_of(batch.report_every) {
+            report(universe, interval_start.elapsed(), interval_generations);
+            interval_start = Instant::now();
+            interval_generations = 0;
+        }
+
+        if let Some(c) = met {
+            break format!("condition {:?} met", c);
+        }
+    };
+
+    let elapsed = start.elapsed();
+    println!(
+        "Stopped: {} at generation {} after {:.3}s",
+        reason,
+        universe.generation(),
+        elapsed.as_secs_f64()
+    );
+    match periodicity {
+        Some(p) => println!("Periodicity: {}", p),
+        None if detector.is_some() => println!("Periodicity: not periodic"),
+        None => {}
+    }
+
+    if let Some(separation) = &batch.objects {
+        let objects = analyse(&universe.live_cells(), universe.rule(), separation);
+        println!("Objects: {}", objects.len());
+        for object in objects {
+            println!("{:>36} {}", object.bounds.to_string(), object.label());
+        }
+    }
+
+    if let (Some(stats), Some(history)) = (&batch.stats, &history) {
+        info!("Saving statistics: {}", stats);
+        history.save_csv(stats)?;
+    }
+
+    if let Some(output) = &batch.output {
+        info!("Saving pattern: {}", output);
+        save_rle_states(output, &universe.cell_states(), &universe.rule())?;
+    }
+
+    if let (Some(recorder), Some(record)) = (recorder, &batch.record) {
+        info!("Saving recording: {}", record.path);
+        let frames = recorder.finish()?;
+        println!("Recorded {} frames to {}", frames, record.path);
+    }
+
+    if let Some(image) = &batch.image {
+        let bounds = universe
+            .bounds()
+            .ok_or(anyhow!("No cells to draw in {image}"))?;
+        info!("Saving image: {}", image);
+        render(universe, &bounds, &batch.image_style)?.save_png(image)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::grid::{GridCoord, Universe};
+
+    #[test]
+    fn parse_conditions() -> Result<()> {
+        assert_eq!("empty".parse::<StopCondition>()?, StopCondition::Empty);
+        assert_eq!(
+            "population>100".parse::<StopCondition>()?,
+            StopCondition::PopulationAbove(100)
+        );
+        assert_eq!(
+            "population< 5".parse::<StopCondition>()?,
+            StopCondition::PopulationBelow(5)
+        );
+        assert_eq!(
+            "periodic".parse::<StopCondition>()?,
+            StopCondition::Periodic
+        );
+        assert!("population=5".parse::<StopCondition>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn run_until_empty() -> Result<()> {
+        let mut universe = Universe::new();
+        universe.grid.set(GridCoord::Valid(0, 0));
+        universe.grid.set(GridCoord::Valid(0, 1));
+
+        let batch = Batch {
+            generations: Some(100),
+            until: Some(StopCondition::Empty),
+            report_every: 10,
+            output: None,
+            objects: None,
+            stats: None,
+            image: None,
+            image_style: Style::default(),
+            record: None,
+        };
+        run(&mut universe, &batch)?;
+
+        assert_eq!(universe.generation, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn run_until_periodic() -> Result<()> {
+        let mut universe = Universe::new();
+        universe.grid.set(GridCoord::Valid(0, 0));
+        universe.grid.set(GridCoord::Valid(0, 1));
+        universe.grid.set(GridCoord::Valid(0, 2));
+
+        let batch = Batch {
+            generations: Some(100),
+            until: Some(StopCondition::Periodic),
+            report_every: 10,
+            output: None,
+            objects: None,
+            stats: None,
+            image: None,
+            image_style: Style::default(),
+            record: None,
+        };
+        run(&mut universe, &batch)?;
+
+        assert_eq!(universe.generation, 2);
+
+        let unlimited = Batch {
+            generations: None,
+            ..batch
+        };
+        assert!(run(&mut universe, &unlimited).is_err());
+
+        Ok(())
+    }
+}
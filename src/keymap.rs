@@ -0,0 +1,518 @@
+use anyhow::{anyhow, Result};
+use ggez::input::keyboard::{KeyCode, KeyMods};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
+pub enum Action {
+    ToggleRun,
+    Step,
+    FasterGps,
+    SlowerGps,
+    CycleSpeed,
+    BiggerStep,
+    SmallerStep,
+    PanUp,
+    PanDown,
+    PanLeft,
+    PanRight,
+    Center,
+    Clear,
+    ToggleGrid,
+    ToggleRulers,
+    ToggleHeader,
+    ToggleHelp,
+    ToggleObjects,
+    ToggleMinimap,
+    CycleColors,
+    CycleTheme,
+    TogglePlot,
+    PlotZoomIn,
+    PlotZoomOut,
+    ExportStats,
+    SavePattern,
+    ExportImage,
+    ExportPatternImage,
+    ToggleRecording,
+    ZoomOut,
+    ZoomIn,
+    FitPattern,
+    FitSelection,
+    ClearSelection,
+    Follow,
+    Randomize,
+    Reroll,
+    EnterSeed,
+    SaveSettings,
+}
+
+// Action names as used in the [keys] section of the settings, with help text.
+const ACTIONS: &[(Action, &str, &str)] = &[
+    (Action::ToggleRun, "toggle-run", "Start/stop the simulation"),
+    (Action::Step, "step", "Advance a single step of generations"),
+    (Action::FasterGps, "faster", "Increase the generation rate"),
+    (Action::SlowerGps, "slower", "Decrease the generation rate"),
+    (
+        Action::CycleSpeed,
+        "cycle-speed",
+        "Run at the target GPS, generations per frame or as fast as possible",
+    ),
+    (
+        Action::BiggerStep,
+        "bigger-step",
+        "Double the generations in each step",
+    ),
+    (
+        Action::SmallerStep,
+        "smaller-step",
+        "Halve the generations in each step",
+    ),
+    (Action::PanUp, "pan-up", "Pan up (more with Shift)"),
+    (Action::PanDown, "pan-down", "Pan down (more with Shift)"),
+    (Action::PanLeft, "pan-left", "Pan left (more with Shift)"),
+    (Action::PanRight, "pan-right", "Pan right (more with Shift)"),
+    (Action::Center, "center", "Center the viewport"),
+    (Action::Clear, "clear", "Clear the universe"),
+    (Action::ToggleGrid, "toggle-grid", "Show/hide the grid"),
+    (
+        Action::ToggleRulers,
+        "toggle-rulers",
+        "Show/hide the coordinate rulers",
+    ),
+    (
+        Action::ToggleHeader,
+        "toggle-header",
+        "Show/hide the header",
+    ),
+    (Action::ToggleHelp, "toggle-help", "Show/hide this help"),
+    (
+        Action::ToggleObjects,
+        "toggle-objects",
+        "Outline and label the objects",
+    ),
+    (
+        Action::ToggleMinimap,
+        "toggle-minimap",
+        "Show/hide the minimap",
+    ),
+    (
+        Action::CycleColors,
+        "cycle-colors",
+        "Colour cells plainly, by age, by changes or with a heat trail",
+    ),
+    (
+        Action::CycleTheme,
+        "cycle-theme",
+        "Switch to the next colour theme",
+    ),
+    (
+        Action::TogglePlot,
+        "toggle-plot",
+        "Show/hide the statistics plot",
+    ),
+    (Action::PlotZoomIn, "plot-zoom-in", "Plot fewer generations"),
+    (
+        Action::PlotZoomOut,
+        "plot-zoom-out",
+        "Plot more generations",
+    ),
+    (
+        Action::ExportStats,
+        "export-stats",
+        "Export the statistics as CSV",
+    ),
+    (
+        Action::SavePattern,
+        "save-pattern",
+        "Save the selection or whole pattern as RLE",
+    ),
+    (
+        Action::ExportImage,
+        "export-image",
+        "Export the selection or view as PNG",
+    ),
+    (
+        Action::ExportPatternImage,
+        "export-pattern-image",
+        "Export the whole pattern as PNG",
+    ),
+    (
+        Action::ToggleRecording,
+        "toggle-recording",
+        "Start/stop recording the selection or view",
+    ),
+    (Action::ZoomOut, "zoom-out", "Zoom out"),
+    (Action::ZoomIn, "zoom-in", "Zoom in"),
+    (
+        Action::FitPattern,
+        "fit-pattern",
+        "Zoom to fit the whole pattern",
+    ),
+    (
+        Action::FitSelection,
+        "fit-selection",
+        "Zoom to fit the selection",
+    ),
+    (
+        Action::ClearSelection,
+        "clear-selection",
+        "Clear the selection",
+    ),
+    (
+        Action::Follow,
+        "follow",
+        "Follow the selection or object at the pointer",
+    ),
+    (
+        Action::Randomize,
+        "randomize",
+        "Fill with a soup from the next seed",
+    ),
+    (
+        Action::Reroll,
+        "reroll",
+        "Refill with the soup from the same seed",
+    ),
+    (
+        Action::EnterSeed,
+        "enter-seed",
+        "Type a seed for the next soup",
+    ),
+    (
+        Action::SaveSettings,
+        "save-settings",
+        "Save the current settings",
+    ),
+];
+
+const DEFAULT_BINDINGS: &[(&str, Action)] = &[
+    ("Space", Action::ToggleRun),
+    ("N", Action::Step),
+    ("Plus", Action::FasterGps),
+    ("Equals", Action::FasterGps),
+    ("Minus", Action::SlowerGps),
+    ("Underline", Action::SlowerGps),
+    ("X", Action::CycleSpeed),
+    ("Period", Action::BiggerStep),
+    ("Comma", Action::SmallerStep),
+    ("Up", Action::PanUp),
+    ("Down", Action::PanDown),
+    ("Left", Action::PanLeft),
+    ("Right", Action::PanRight),
+    ("C", Action::Center),
+    ("Delete", Action::Clear),
+    ("Back", Action::Clear),
+    ("G", Action::ToggleGrid),
+    ("Shift+G", Action::ToggleRulers),
+    ("H", Action::ToggleHeader),
+    ("F1", Action::ToggleHelp),
+    ("Shift+Slash", Action::ToggleHelp),
+    ("O", Action::ToggleObjects),
+    ("M", Action::ToggleMinimap),
+    ("V", Action::CycleColors),
+    ("T", Action::CycleTheme),
+    ("P", Action::TogglePlot),
+    ("RBracket", Action::PlotZoomIn),
+    ("LBracket", Action::PlotZoomOut),
+    ("Ctrl+E", Action::ExportStats),
+    ("Ctrl+Shift+S", Action::SavePattern),
+    ("Ctrl+I", Action::ExportImage),
+    ("Ctrl+Shift+I", Action::ExportPatternImage),
+    ("Ctrl+G", Action::ToggleRecording),
+    ("A", Action::ZoomOut),
+    ("S", Action::ZoomIn),
+    ("Z", Action::FitPattern),
+    ("Shift+Z", Action::FitSelection),
+    ("Escape", Action::ClearSelection),
+    ("F", Action::Follow),
+    ("R", Action::Randomize),
+    ("Shift+R", Action::Reroll),
+    ("Ctrl+R", Action::EnterSeed),
+    ("Ctrl+S", Action::SaveSettings),
+];
+
+// Modifier names accepted in key bindings, in display order.
+const MODS: &[(KeyMods, &str)] = &[
+    (KeyMods::CTRL, "Ctrl"),
+    (KeyMods::ALT, "Alt"),
+    (KeyMods::SHIFT, "Shift"),
+    (KeyMods::LOGO, "Logo"),
+];
+
+// Key names accepted in the settings. Names match the ggez KeyCode variants.
+const KEYS: &[(KeyCode, &str)] = &[
+    (KeyCode::A, "A"),
+    (KeyCode::B, "B"),
+    (KeyCode::C, "C"),
+    (KeyCode::D, "D"),
+    (KeyCode::E, "E"),
+    (KeyCode::F, "F"),
+    (KeyCode::G, "G"),
+    (KeyCode::H, "H"),
+    (KeyCode::I, "I"),
+    (KeyCode::J, "J"),
+    (KeyCode::K, "K"),
+    (KeyCode::L, "L"),
+    (KeyCode::M, "M"),
+    (KeyCode::N, "N"),
+    (KeyCode::O, "O"),
+    (KeyCode::P, "P"),
+    (KeyCode::Q, "Q"),
+    (KeyCode::R, "R"),
+    (KeyCode::S, "S"),
+    (KeyCode::T, "T"),
+    (KeyCode::U, "U"),
+    (KeyCode::V, "V"),
+    (KeyCode::W, "W"),
+    (KeyCode::X, "X"),
+    (KeyCode::Y, "Y"),
+    (KeyCode::Z, "Z"),
+    (KeyCode::Key0, "Key0"),
+    (KeyCode::Key1, "Key1"),
+    (KeyCode::Key2, "Key2"),
+    (KeyCode::Key3, "Key3"),
+    (KeyCode::Key4, "Key4"),
+    (KeyCode::Key5, "Key5"),
+    (KeyCode::Key6, "Key6"),
+    (KeyCode::Key7, "Key7"),
+    (KeyCode::Key8, "Key8"),
+    (KeyCode::Key9, "Key9"),
+    (KeyCode::F1, "F1"),
+    (KeyCode::F2, "F2"),
+    (KeyCode::F3, "F3"),
+    (KeyCode::F4, "F4"),
+    (KeyCode::F5, "F5"),
+    (KeyCode::F6, "F6"),
+    (KeyCode::F7, "F7"),
+    (KeyCode::F8, "F8"),
+    (KeyCode::F9, "F9"),
+    (KeyCode::F10, "F10"),
+    (KeyCode::F11, "F11"),
+    (KeyCode::F12, "F12"),
+    (KeyCode::Up, "Up"),
+    (KeyCode::Down, "Down"),
+    (KeyCode::Left, "Left"),
+    (KeyCode::Right, "Right"),
+    (KeyCode::Space, "Space"),
+    (KeyCode::Return, "Return"),
+    (KeyCode::Tab, "Tab"),
+    (KeyCode::Escape, "Escape"),
+    (KeyCode::Back, "Back"),
+    (KeyCode::Delete, "Delete"),
+    (KeyCode::Insert, "Insert"),
+    (KeyCode::Home, "Home"),
+    (KeyCode::End, "End"),
+    (KeyCode::PageUp, "PageUp"),
+    (KeyCode::PageDown, "PageDown"),
+    (KeyCode::Plus, "Plus"),
+    (KeyCode::Minus, "Minus"),
+    (KeyCode::Equals, "Equals"),
+    (KeyCode::Underline, "Underline"),
+    (KeyCode::Comma, "Comma"),
+    (KeyCode::Period, "Period"),
+    (KeyCode::Slash, "Slash"),
+    (KeyCode::Backslash, "Backslash"),
+    (KeyCode::Semicolon, "Semicolon"),
+    (KeyCode::Apostrophe, "Apostrophe"),
+    (KeyCode::Grave, "Grave"),
+    (KeyCode::LBracket, "LBracket"),
+    (KeyCode::RBracket, "RBracket"),
+];
+
+impl Action {
+    pub fn from_name(name: &str) -> Result<Action> {
+        ACTIONS
+            .iter()
+            .find(|(_, n, _)| *n == name)
+            .map(|(a, _, _)| *a)
+            .ok_or(anyhow!("Unknown action: {name}"))
+    }
+}
+
+pub fn key_name(key: KeyCode) -> String {
+    KEYS.iter()
+        .find(|(k, _)| *k == key)
+        .map(|(_, n)| n.to_string())
+        .unwrap_or_else(|| format!("{:?}", key))
+}
+
+pub fn key_from_name(name: &str) -> Result<KeyCode> {
+    KEYS.iter()
+        .find(|(_, n)| n.eq_ignore_ascii_case(name))
+        .map(|(k, _)| *k)
+        .ok_or(anyhow!("Unknown key: {name}"))
+}
+
+// A key together with the modifiers which must be held.
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
+pub struct Binding {
+    key: KeyCode,
+    // KeyMods bits, as KeyMods does not implement Hash.
+    mods: u8,
+}
+
+impl Binding {
+    pub fn new(key: KeyCode, mods: KeyMods) -> Self {
+        Binding {
+            key,
+            mods: mods.bits(),
+        }
+    }
+
+    fn mods(&self) -> KeyMods {
+        KeyMods::from_bits_truncate(self.mods)
+    }
+}
+
+impl FromStr for Binding {
+    type Err = anyhow::Error;
+
+    // Parse bindings such as "S", "Ctrl+S" or "Ctrl+Shift+Up".
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts: Vec<&str> = s.split('+').map(str::trim).collect();
+        let key = key_from_name(parts.pop().unwrap_or_default())?;
+
+        let mut mods = KeyMods::empty();
+        for part in parts {
+            mods |= MODS
+                .iter()
+                .find(|(_, n)| n.eq_ignore_ascii_case(part))
+                .map(|(m, _)| *m)
+                .ok_or(anyhow!("Unknown modifier: {part}"))?;
+        }
+
+        Ok(Binding::new(key, mods))
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (m, n) in MODS {
+            if self.mods().contains(*m) {
+                write!(f, "{}+", n)?;
+            }
+        }
+        write!(f, "{}", key_name(self.key))
+    }
+}
+
+#[derive(Debug)]
+pub struct Keymap {
+    bindings: HashMap<Binding, Action>,
+}
+
+impl Keymap {
+    // Build the keymap from the defaults, replacing the keys for any action named in overrides.
+    pub fn new(overrides: &BTreeMap<String, Vec<String>>) -> Result<Self> {
+        let mut bindings: HashMap<Binding, Action> = HashMap::new();
+        for (b, a) in DEFAULT_BINDINGS {
+            bindings.insert(b.parse()?, *a);
+        }
+
+        for (name, keys) in overrides {
+            let action = Action::from_name(name)?;
+            bindings.retain(|_, a| *a != action);
+            for key in keys {
+                bindings.insert(key.parse()?, action);
+            }
+        }
+
+        Ok(Keymap { bindings })
+    }
+
+    // Find the action for a key press. Shift is ignored if there is no exact binding, as it
+    // is used to modify some actions (e.g. larger pans).
+    pub fn lookup(&self, key: KeyCode, mods: KeyMods) -> Option<Action> {
+        self.bindings
+            .get(&Binding::new(key, mods))
+            .or_else(|| {
+                self.bindings
+                    .get(&Binding::new(key, mods.difference(KeyMods::SHIFT)))
+            })
+            .copied()
+    }
+
+    fn bindings_for(&self, action: Action) -> Vec<String> {
+        let mut keys: Vec<String> = self
+            .bindings
+            .iter()
+            .filter(|(_, a)| **a == action)
+            .map(|(b, _)| b.to_string())
+            .collect();
+        keys.sort();
+
+        keys
+    }
+
+    // Bindings and description for each action, for the help overlay.
+    pub fn help(&self) -> Vec<(String, &'static str)> {
+        ACTIONS
+            .iter()
+            .map(|(a, _, d)| (self.bindings_for(*a).join(", "), *d))
+            .collect()
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::new(&BTreeMap::new()).expect("Valid default keymap")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn override_bindings() -> Result<()> {
+        let mut overrides = BTreeMap::new();
+        overrides.insert("zoom-in".to_owned(), vec!["z".to_owned(), "X".to_owned()]);
+
+        let keymap = Keymap::new(&overrides)?;
+
+        let none = KeyMods::empty();
+        assert_eq!(keymap.lookup(KeyCode::S, none), None);
+        assert_eq!(keymap.lookup(KeyCode::Z, none), Some(Action::ZoomIn));
+        assert_eq!(keymap.lookup(KeyCode::X, none), Some(Action::ZoomIn));
+        assert_eq!(keymap.lookup(KeyCode::A, none), Some(Action::ZoomOut));
+
+        Ok(())
+    }
+
+    #[test]
+    fn modifiers() -> Result<()> {
+        let keymap = Keymap::default();
+
+        assert_eq!(
+            keymap.lookup(KeyCode::S, KeyMods::CTRL),
+            Some(Action::SaveSettings)
+        );
+        assert_eq!(
+            keymap.lookup(KeyCode::S, KeyMods::empty()),
+            Some(Action::ZoomIn)
+        );
+        assert_eq!(
+            keymap.lookup(KeyCode::Up, KeyMods::SHIFT),
+            Some(Action::PanUp)
+        );
+        assert_eq!(keymap.lookup(KeyCode::Up, KeyMods::CTRL), None);
+
+        let binding: Binding = "shift+ctrl+up".parse()?;
+        assert_eq!(binding.to_string(), "Ctrl+Shift+Up");
+        assert!("Hyper+Up".parse::<Binding>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn reject_unknown() {
+        let mut overrides = BTreeMap::new();
+        overrides.insert("explode".to_owned(), vec!["Z".to_owned()]);
+        assert!(Keymap::new(&overrides).is_err());
+
+        let mut overrides = BTreeMap::new();
+        overrides.insert("zoom-in".to_owned(), vec!["NoSuchKey".to_owned()]);
+        assert!(Keymap::new(&overrides).is_err());
+    }
+}
@@ -0,0 +1,52 @@
+//! Boundlesse - a boundless Game of Life engine.
+//!
+//! The library provides the simulation engine used by the `boundlesse` app:
+//!
+//! - [`grid`]: the universe implementations and the [`grid::Life`] API over them.
+//! - [`rule`]: Life-like rules in B/S notation, and Generations rules in B/S/C notation.
+//! - [`rle`]: reading and writing RLE encoded patterns.
+//! - [`headless`]: running a universe without graphics, reporting progress.
+//! - [`period`]: detecting still lifes, oscillators and spaceships.
+//! - [`objects`]: separating a pattern into its constituent objects and identifying them.
+//! - [`apgcode`]: naming objects by apgcode.
+//! - [`soup`]: searching random soups and taking a census of the objects left.
+//! - [`stats`]: recording statistics for each generation.
+//! - [`density`]: counting live cells in blocks, for drawing zoomed out views.
+//! - [`ages`]: tracking when cells were born and died, for colouring by history.
+//! - [`render`]: rendering the universe to PNG images in software.
+//! - [`record`]: recording animations as GIFs or numbered PNG files.
+//!
+//! The graphical app is built with the `gui` feature (on by default). Library
+//! users can disable default features to avoid depending on ggez.
+//!
+//! ```
+//! use boundlesse::grid::{Backend, GridCoord};
+//! use boundlesse::rule::Rule;
+//!
+//! let mut universe = Backend::Generations.create(Rule::default());
+//!
+//! // A blinker.
+//! for y in 0..3 {
+//!     universe.set(GridCoord::Valid(0, y));
+//! }
+//!
+//! let stats = universe.update();
+//! assert_eq!((stats.population, stats.births, stats.deaths), (3, 2, 2));
+//!
+//! assert!(universe.is_alive(&GridCoord::Valid(-1, 1)));
+//! assert!(!universe.is_alive(&GridCoord::Valid(0, 0)));
+//! ```
+
+pub mod ages;
+pub mod apgcode;
+pub mod density;
+pub mod grid;
+pub mod headless;
+pub mod objects;
+pub mod period;
+pub mod record;
+pub mod render;
+pub mod rle;
+pub mod rule;
+pub mod soup;
+pub mod stats;
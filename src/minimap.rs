@@ -0,0 +1,101 @@
+use ggez::glam::*;
+use ggez::graphics::Rect;
+
+use boundlesse::grid::Bounds;
+
+// Longest side of the minimap in pixels, and its distance from the window corner.
+const SIZE: f64 = 200.0;
+const MARGIN: f32 = 20.0;
+
+// Space left around the populated extent, as a fraction of its size.
+const PADDING: f64 = 0.05;
+
+// Where the minimap is drawn in the bottom left corner, and the part of the grid it shows.
+#[derive(Debug, Clone, Copy)]
+pub struct Layout {
+    pub rect: Rect,
+    extent: Bounds,
+    // Pixels per cell.
+    pub scale: f64,
+}
+
+impl Layout {
+    // Show the populated extent and the viewport, so the viewport is always on the map.
+    pub fn new(populated: Option<Bounds>, visible: Bounds, window_size: (f32, f32)) -> Self {
+        let mut extent = visible;
+        if let Some(p) = populated {
+            extent.include(p.min_x, p.min_y);
+            extent.include(p.max_x, p.max_y);
+        }
+        let pad_x = (extent.width() as f64 * PADDING) as i64;
+        let pad_y = (extent.height() as f64 * PADDING) as i64;
+        extent.include(extent.min_x - pad_x, extent.min_y - pad_y);
+        extent.include(extent.max_x + pad_x, extent.max_y + pad_y);
+
+        let scale = SIZE / extent.width().max(extent.height()) as f64;
+        let (w, h) = (
+            (extent.width() as f64 * scale) as f32,
+            (extent.height() as f64 * scale) as f32,
+        );
+        Layout {
+            rect: Rect::new(MARGIN, window_size.1 - MARGIN - h, w, h),
+            extent,
+            scale,
+        }
+    }
+
+    pub fn extent(&self) -> Bounds {
+        self.extent
+    }
+
+    pub fn contains(&self, p: Vec2) -> bool {
+        self.rect.contains(p)
+    }
+
+    pub fn screen_to_grid(&self, p: Vec2) -> (f64, f64) {
+        (
+            self.extent.min_x as f64 + (p.x - self.rect.x) as f64 / self.scale,
+            self.extent.min_y as f64 + (p.y - self.rect.y) as f64 / self.scale,
+        )
+    }
+
+    pub fn grid_to_screen(&self, x: f64, y: f64) -> Vec2 {
+        vec2(
+            self.rect.x + ((x - self.extent.min_x as f64) * self.scale) as f32,
+            self.rect.y + ((y - self.extent.min_y as f64) * self.scale) as f32,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layout() {
+        let visible = Bounds {
+            min_x: -40,
+            min_y: -30,
+            max_x: 39,
+            max_y: 29,
+        };
+        let populated = Bounds {
+            min_x: 0,
+            min_y: 0,
+            max_x: 319,
+            max_y: 9,
+        };
+        let layout = Layout::new(Some(populated), visible, (800.0, 600.0));
+
+        // The extent covers both, padded.
+        let extent = layout.extent();
+        assert_eq!((extent.min_x, extent.max_x), (-58, 337));
+        assert!((layout.rect.w - 200.0).abs() < 1e-3);
+        assert!((layout.rect.bottom() - 580.0).abs() < 1e-3);
+
+        let p = layout.grid_to_screen(100.0, 0.0);
+        assert!(layout.contains(p));
+        let (x, y) = layout.screen_to_grid(p);
+        assert!((x - 100.0).abs() < 1e-3 && y.abs() < 1e-3);
+    }
+}
@@ -0,0 +1,621 @@
+//! Separating a pattern into its constituent objects and identifying them.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::OnceLock;
+
+use crate::apgcode::apgcode;
+use crate::grid::{Bounds, GridCoord, Life, Universe};
+use crate::period::{PeriodDetector, Periodicity};
+use crate::rule::Rule;
+
+/// Which cells around a cell count as touching it.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Neighbourhood {
+    /// The surrounding square, including diagonals.
+    #[default]
+    Moore,
+    /// The surrounding diamond, excluding diagonals.
+    VonNeumann,
+}
+
+impl Neighbourhood {
+    fn within(&self, dx: i64, dy: i64, distance: i64) -> bool {
+        match self {
+            Neighbourhood::Moore => dx.abs().max(dy.abs()) <= distance,
+            Neighbourhood::VonNeumann => dx.abs() + dy.abs() <= distance,
+        }
+    }
+}
+
+/// How a pattern is separated into objects.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Separation {
+    // Touching cells form islands.
+    pub neighbourhood: Neighbourhood,
+    // Islands which interact are merged with those within this distance.
+    pub merge_distance: i64,
+    // Generations checked for interactions, 0 to take the islands as they are.
+    pub generations: usize,
+}
+
+impl Default for Separation {
+    fn default() -> Self {
+        Separation {
+            neighbourhood: Neighbourhood::Moore,
+            merge_distance: 2,
+            generations: 8,
+        }
+    }
+}
+
+/// Partition the cells into islands, where cells within `distance` of each other in the
+/// neighbourhood belong to the same island.
+pub fn separate(
+    cells: &[GridCoord],
+    neighbourhood: Neighbourhood,
+    distance: i64,
+) -> Vec<Vec<GridCoord>> {
+    let mut remaining: HashSet<GridCoord> = cells.iter().copied().collect();
+    let mut islands: Vec<Vec<GridCoord>> = vec![];
+
+    for &start in cells {
+        if !remaining.remove(&start) {
+            continue;
+        }
+
+        let mut island = vec![start];
+        let mut next = 0;
+        while next < island.len() {
+            let c = island[next];
+            next += 1;
+
+            for dy in -distance..=distance {
+                for dx in -distance..=distance {
+                    let n = c.adjust(dx, dy);
+                    if neighbourhood.within(dx, dy, distance) && remaining.remove(&n) {
+                        island.push(n);
+                    }
+                }
+            }
+        }
+
+        islands.push(island);
+    }
+
+    islands
+}
+
+/// Separate the cells into objects which evolve independently under the rule. Touching cells
+/// form islands, then islands which behave differently when run alone are merged with their
+/// neighbours.
+pub fn separate_independent(
+    cells: &[GridCoord],
+    rule: Rule,
+    separation: &Separation,
+) -> Vec<Vec<GridCoord>> {
+    let islands = separate(cells, separation.neighbourhood, 1);
+
+    let universe = |cells: &[GridCoord]| {
+        let mut universe = Universe::with_rule(rule);
+        for &c in cells {
+            universe.grid.set(c);
+        }
+        universe
+    };
+    let mut whole = universe(cells);
+    let mut alone: Vec<Universe> = islands.iter().map(|i| universe(i)).collect();
+    let mut interacting: HashSet<usize> = HashSet::new();
+
+    // Which islands have live cells at each position, when run alone.
+    let owners = |alone: &[Universe]| {
+        let mut owners: HashMap<GridCoord, Vec<usize>> = HashMap::new();
+        for (i, u) in alone.iter().enumerate() {
+            for c in u.live_cells() {
+                owners.entry(c).or_default().push(i);
+            }
+        }
+        owners
+    };
+
+    for _ in 0..separation.generations {
+        let before = owners(&alone);
+        whole.update();
+        alone.iter_mut().for_each(|u| {
+            u.update();
+        });
+        let after = owners(&alone);
+
+        // Cells where the islands alone disagree with the whole pattern.
+        let expected: HashSet<GridCoord> = whole.live_cells().into_iter().collect();
+        let wrong = expected
+            .iter()
+            .filter(|c| after.get(c).is_none_or(|o| o.len() > 1))
+            .chain(after.keys().filter(|c| !expected.contains(c)));
+
+        // Every island with a cell neighbouring a wrong one contributed to it.
+        for c in wrong {
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    if let Some(o) = before.get(&c.adjust(dx, dy)) {
+                        interacting.extend(o);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut objects = vec![];
+    let mut merging = vec![];
+    for (i, island) in islands.into_iter().enumerate() {
+        if interacting.contains(&i) {
+            merging.extend(island);
+        } else {
+            objects.push(island);
+        }
+    }
+    objects.extend(separate(
+        &merging,
+        separation.neighbourhood,
+        separation.merge_distance,
+    ));
+
+    objects
+}
+
+/// A rotation (clockwise, with y increasing downwards), optionally after flipping left to right.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct Orientation {
+    pub flipped: bool,
+    pub rotation: u16,
+}
+
+impl Orientation {
+    fn all() -> impl Iterator<Item = Orientation> {
+        [false, true].into_iter().flat_map(|flipped| {
+            [0, 90, 180, 270]
+                .into_iter()
+                .map(move |rotation| Orientation { flipped, rotation })
+        })
+    }
+
+    pub fn apply(&self, x: i64, y: i64) -> (i64, i64) {
+        let x = if self.flipped { -x } else { x };
+        match self.rotation {
+            90 => (-y, x),
+            180 => (-x, -y),
+            270 => (y, -x),
+            _ => (x, y),
+        }
+    }
+}
+
+impl fmt::Display for Orientation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flip = if self.flipped { 'F' } else { 'R' };
+        write!(f, "{}{}", flip, self.rotation)
+    }
+}
+
+// Well known B3/S23 objects, in their first phase and reference orientation.
+const KNOWN: &[(&str, &[&str])] = &[
+    ("block", &["oo", "oo"]),
+    ("beehive", &[".oo.", "o..o", ".oo."]),
+    ("loaf", &[".oo.", "o..o", ".o.o", "..o."]),
+    ("boat", &["oo.", "o.o", ".o."]),
+    ("ship", &["oo.", "o.o", ".oo"]),
+    ("tub", &[".o.", "o.o", ".o."]),
+    ("pond", &[".oo.", "o..o", "o..o", ".oo."]),
+    ("long boat", &["oo..", "o.o.", ".o.o", "..o."]),
+    ("barge", &[".o..", "o.o.", ".o.o", "..o."]),
+    ("eater", &["oo..", "o.o.", "..o.", "..oo"]),
+    ("blinker", &["ooo"]),
+    ("toad", &[".ooo", "ooo."]),
+    ("beacon", &["oo..", "oo..", "..oo", "..oo"]),
+    (
+        "pulsar",
+        &[
+            "..ooo...ooo..",
+            ".............",
+            "o....o.o....o",
+            "o....o.o....o",
+            "o....o.o....o",
+            "..ooo...ooo..",
+            ".............",
+            "..ooo...ooo..",
+            "o....o.o....o",
+            "o....o.o....o",
+            "o....o.o....o",
+            ".............",
+            "..ooo...ooo..",
+        ],
+    ),
+    (
+        "pentadecathlon",
+        &["..o....o..", "oo.oooo.oo", "..o....o.."],
+    ),
+    ("glider", &[".o.", "..o", "ooo"]),
+    ("LWSS", &[".o..o", "o....", "o...o", "oooo."]),
+    ("MWSS", &["...o..", ".o...o", "o.....", "o....o", "ooooo."]),
+    (
+        "HWSS",
+        &["...oo..", ".o....o", "o......", "o.....o", "oooooo."],
+    ),
+];
+
+/// A well known object, with the phase and orientation it was found in.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct Known {
+    pub name: &'static str,
+    pub period: usize,
+    pub phase: usize,
+    pub orientation: Orientation,
+}
+
+impl fmt::Display for Known {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.period > 1 {
+            write!(
+                f,
+                "{} {}/{} {}",
+                self.name, self.phase, self.period, self.orientation
+            )
+        } else {
+            write!(f, "{} {}", self.name, self.orientation)
+        }
+    }
+}
+
+// Translate the points so their top left is at 0,0, in a canonical order.
+fn normalise(points: impl Iterator<Item = (i64, i64)>) -> Vec<(i64, i64)> {
+    let mut points: Vec<(i64, i64)> = points.collect();
+    let min_x = points.iter().map(|p| p.0).min().unwrap_or(0);
+    let min_y = points.iter().map(|p| p.1).min().unwrap_or(0);
+    points
+        .iter_mut()
+        .for_each(|p| *p = (p.0 - min_x, p.1 - min_y));
+    points.sort();
+
+    points
+}
+
+fn valid(cells: &[GridCoord]) -> impl Iterator<Item = (i64, i64)> + '_ {
+    cells.iter().filter_map(|c| match *c {
+        GridCoord::Valid(x, y) => Some((x, y)),
+        GridCoord::OutOfBounds => None,
+    })
+}
+
+// Every phase and orientation of the known objects. Where shapes coincide (e.g. for symmetric
+// objects) the earliest phase and first orientation is used.
+fn catalogue() -> &'static HashMap<Vec<(i64, i64)>, Known> {
+    static CATALOGUE: OnceLock<HashMap<Vec<(i64, i64)>, Known>> = OnceLock::new();
+
+    CATALOGUE.get_or_init(|| {
+        let mut catalogue = HashMap::new();
+        for (name, rows) in KNOWN {
+            let mut universe = Universe::new();
+            for (y, row) in rows.iter().enumerate() {
+                for (x, c) in row.chars().enumerate() {
+                    if c == 'o' {
+                        universe.grid.set(GridCoord::Valid(x as i64, y as i64));
+                    }
+                }
+            }
+
+            let mut detector = PeriodDetector::new();
+            let mut phases = vec![universe.live_cells()];
+            detector.observe(&universe);
+            let period = loop {
+                universe.update();
+                match detector.observe(&universe) {
+                    Some(Periodicity::StillLife) => break 1,
+                    Some(Periodicity::Oscillator { period })
+                    | Some(Periodicity::Spaceship { period, .. }) => break period,
+                    Some(Periodicity::Empty) => panic!("Known object {name} dies"),
+                    None if universe.generation > 100 => panic!("Known object {name} unstable"),
+                    None => phases.push(universe.live_cells()),
+                }
+            };
+
+            for (phase, cells) in phases.iter().take(period).enumerate() {
+                for orientation in Orientation::all() {
+                    let shape = normalise(valid(cells).map(|(x, y)| orientation.apply(x, y)));
+                    catalogue.entry(shape).or_insert(Known {
+                        name,
+                        period,
+                        phase,
+                        orientation,
+                    });
+                }
+            }
+        }
+
+        catalogue
+    })
+}
+
+/// Look the object up in the table of well known B3/S23 objects.
+pub fn identify(cells: &[GridCoord]) -> Option<Known> {
+    catalogue().get(&normalise(valid(cells))).copied()
+}
+
+// Longest period looked for when naming unknown objects.
+const MAX_UNKNOWN_PERIOD: usize = 30;
+
+/// An object found in a pattern.
+#[derive(Debug, Clone)]
+pub struct Object {
+    pub cells: Vec<GridCoord>,
+    pub bounds: Bounds,
+    pub known: Option<Known>,
+    // The known object's name, otherwise its apgcode.
+    pub name: String,
+}
+
+impl Object {
+    /// Short description, e.g. "glider 2/4 R90".
+    pub fn label(&self) -> String {
+        match &self.known {
+            Some(known) => known.to_string(),
+            None => self.name.clone(),
+        }
+    }
+}
+
+/// Separate the cells into objects and identify each one, ordered by position.
+pub fn analyse(cells: &[GridCoord], rule: Rule, separation: &Separation) -> Vec<Object> {
+    let mut objects: Vec<Object> = separate_independent(cells, rule, separation)
+        .into_iter()
+        .filter_map(|cells| {
+            let bounds = Bounds::of(&cells)?;
+            // The known objects are only known in Life.
+            let known = if rule == Rule::default() {
+                identify(&cells)
+            } else {
+                None
+            };
+            let name = match known {
+                Some(k) => k.name.to_owned(),
+                None => apgcode(&cells, rule, MAX_UNKNOWN_PERIOD),
+            };
+
+            Some(Object {
+                cells,
+                bounds,
+                known,
+                name,
+            })
+        })
+        .collect();
+    objects.sort_by_key(|o| (o.bounds.min_y, o.bounds.min_x));
+
+    objects
+}
+
+/// Follows a moving object, e.g. a spaceship or puffer, by keeping a box of its size
+/// centred on the live cells in and around the box.
+#[derive(Debug, Clone)]
+pub struct Tracker {
+    bounds: Bounds,
+}
+
+impl Tracker {
+    pub fn new(bounds: Bounds) -> Self {
+        Tracker { bounds }
+    }
+
+    pub fn bounds(&self) -> Bounds {
+        self.bounds
+    }
+
+    pub fn centre(&self) -> (f64, f64) {
+        (
+            (self.bounds.min_x + self.bounds.max_x + 1) as f64 / 2.0,
+            (self.bounds.min_y + self.bounds.max_y + 1) as f64 / 2.0,
+        )
+    }
+
+    /// Move with the object after `generations` more generations, returning false if it has gone.
+    pub fn track(&mut self, universe: &dyn Life, generations: usize) -> bool {
+        // Nothing moves faster than a cell a generation.
+        let reach = generations.max(1) as i64;
+        let b = self.bounds;
+        let reachable = Bounds {
+            min_x: b.min_x - reach,
+            min_y: b.min_y - reach,
+            max_x: b.max_x + reach,
+            max_y: b.max_y + reach,
+        };
+        let near: Vec<(i64, i64)> = universe
+            .cells_in(&reachable)
+            .into_iter()
+            .filter_map(|c| match c {
+                GridCoord::Valid(x, y) => Some((x, y)),
+                _ => None,
+            })
+            .collect();
+        if near.is_empty() {
+            return false;
+        }
+
+        // Recentre on the cells' centroid.
+        let n = near.len() as f64;
+        let cx = near.iter().map(|&(x, _)| x as f64 + 0.5).sum::<f64>() / n;
+        let cy = near.iter().map(|&(_, y)| y as f64 + 0.5).sum::<f64>() / n;
+        let (bx, by) = self.centre();
+        let dx = ((cx - bx).round() as i64).clamp(-reach, reach);
+        let dy = ((cy - by).round() as i64).clamp(-reach, reach);
+        self.bounds = Bounds {
+            min_x: b.min_x + dx,
+            min_y: b.min_y + dy,
+            max_x: b.max_x + dx,
+            max_y: b.max_y + dy,
+        };
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn separate_islands() {
+        let cells = [
+            // Block
+            GridCoord::Valid(0, 0),
+            GridCoord::Valid(1, 0),
+            GridCoord::Valid(0, 1),
+            GridCoord::Valid(1, 1),
+            // Blinker, 3 cells away
+            GridCoord::Valid(4, 0),
+            GridCoord::Valid(4, 1),
+            GridCoord::Valid(4, 2),
+        ];
+
+        let mut islands = separate(&cells, Neighbourhood::Moore, 1);
+        islands.iter_mut().for_each(|i| i.sort());
+        islands.sort();
+
+        let mut block = cells[0..4].to_vec();
+        block.sort();
+        assert_eq!(islands, vec![block, cells[4..7].to_vec()]);
+
+        assert_eq!(separate(&cells, Neighbourhood::Moore, 3).len(), 1);
+
+        let diagonal = [GridCoord::Valid(0, 0), GridCoord::Valid(1, 1)];
+        assert_eq!(separate(&diagonal, Neighbourhood::Moore, 1).len(), 1);
+        assert_eq!(separate(&diagonal, Neighbourhood::VonNeumann, 1).len(), 2);
+    }
+
+    #[test]
+    fn merge_interacting() {
+        // Toad, in the phase where its halves don't touch, and a distant block.
+        let cells = [
+            GridCoord::Valid(2, 0),
+            GridCoord::Valid(0, 1),
+            GridCoord::Valid(3, 1),
+            GridCoord::Valid(0, 2),
+            GridCoord::Valid(3, 2),
+            GridCoord::Valid(1, 3),
+            GridCoord::Valid(10, 0),
+            GridCoord::Valid(11, 0),
+            GridCoord::Valid(10, 1),
+            GridCoord::Valid(11, 1),
+        ];
+        assert_eq!(separate(&cells, Neighbourhood::Moore, 1).len(), 3);
+
+        let mut objects = separate_independent(&cells, Rule::default(), &Separation::default());
+        objects.sort_by_key(|o| o.len());
+        assert_eq!(objects.len(), 2);
+        assert_eq!(objects[0].len(), 4);
+        assert_eq!(objects[1].len(), 6);
+    }
+
+    #[test]
+    fn identify_known() {
+        // Glider, rotated a quarter turn and a generation on.
+        let glider = [(0, 1), (1, 2), (2, 0), (2, 1), (2, 2)];
+        let cells: Vec<GridCoord> = glider
+            .iter()
+            .map(|&(x, y)| GridCoord::Valid(x + 5, y - 3))
+            .collect();
+        let known = identify(&cells).expect("Glider known");
+        assert_eq!(known.name, "glider");
+        assert_eq!(known.period, 4);
+
+        // Check the phase and orientation by recreating the reference phase.
+        let mut universe = Universe::new();
+        for &(x, y) in &glider {
+            universe.grid.set(GridCoord::Valid(x, y));
+        }
+        for _ in 0..(4 - known.phase) % 4 {
+            universe.update();
+        }
+        let found: Vec<(i64, i64)> = normalise(valid(&universe.live_cells()));
+        let reference = normalise(
+            [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)]
+                .into_iter()
+                .map(|(x, y)| known.orientation.apply(x, y)),
+        );
+        assert_eq!(found, reference);
+
+        assert_eq!(identify(&[GridCoord::Valid(0, 0)]), None);
+    }
+
+    #[test]
+    fn track_glider() {
+        let mut universe = Universe::new();
+        let glider = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+        for &(x, y) in &glider {
+            universe.grid.set(GridCoord::Valid(x, y));
+        }
+        // A block left behind isn't followed.
+        for &(x, y) in &[(-4, -4), (-3, -4), (-4, -3), (-3, -3)] {
+            universe.grid.set(GridCoord::Valid(x, y));
+        }
+
+        let mut tracker = Tracker::new(Bounds::of(&valid_coords(&glider)).unwrap());
+        for _ in 0..40 {
+            universe.update();
+            assert!(tracker.track(&universe, 1));
+        }
+
+        // The glider has moved 10 cells diagonally, and is still in view.
+        let b = tracker.bounds();
+        assert_eq!((b.width(), b.height()), (3, 3));
+        assert!((tracker.centre().0 - 11.5).abs() <= 1.0);
+        assert!((tracker.centre().1 - 11.5).abs() <= 1.0);
+
+        universe = Universe::new();
+        assert!(!tracker.track(&universe, 1));
+    }
+
+    fn valid_coords(cells: &[(i64, i64)]) -> Vec<GridCoord> {
+        cells.iter().map(|&(x, y)| GridCoord::Valid(x, y)).collect()
+    }
+
+    #[test]
+    fn analyse_objects() {
+        let mut cells = vec![];
+        for &(x, y) in &[(0, 0), (1, 0), (0, 1), (1, 1)] {
+            cells.push(GridCoord::Valid(x, y));
+        }
+        for y in 0..3 {
+            cells.push(GridCoord::Valid(10, y));
+        }
+        // Snake, not in the table of well known objects.
+        for &(x, y) in &[(20, 0), (21, 0), (23, 0), (20, 1), (22, 1), (23, 1)] {
+            cells.push(GridCoord::Valid(x, y));
+        }
+
+        let objects = analyse(&cells, Rule::default(), &Separation::default());
+        let labels: Vec<String> = objects.iter().map(Object::label).collect();
+        assert_eq!(labels[..2], ["block R0", "blinker 0/2 R90"]);
+        assert!(objects[2].known.is_none());
+        assert!(labels[2].starts_with("xs6_"), "{}", labels[2]);
+        assert_eq!(objects[1].bounds.to_string(), "(10,0)-(10,2) 1x3");
+    }
+
+    #[test]
+    fn catalogue_periods() {
+        let period = |name| {
+            catalogue()
+                .values()
+                .find(|k| k.name == name)
+                .map(|k| k.period)
+        };
+
+        assert_eq!(period("block"), Some(1));
+        assert_eq!(period("beacon"), Some(2));
+        assert_eq!(period("pulsar"), Some(3));
+        assert_eq!(period("pentadecathlon"), Some(15));
+        assert_eq!(period("HWSS"), Some(4));
+        assert!(KNOWN.iter().all(|(name, _)| period(name).is_some()));
+    }
+}
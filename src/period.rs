@@ -0,0 +1,326 @@
+//! Detecting still lifes, oscillators and spaceships.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+use crate::grid::{Bounds, GridCoord, Life};
+
+/// Number of generations of history kept, limiting the longest period detected.
+pub const DEFAULT_MAX_PERIOD: usize = 1000;
+
+/// The repeating behaviour of a pattern.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Periodicity {
+    Empty,
+    StillLife,
+    Oscillator { period: usize },
+    Spaceship { period: usize, dx: i64, dy: i64 },
+}
+
+impl fmt::Display for Periodicity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Periodicity::Empty => write!(f, "empty"),
+            Periodicity::StillLife => write!(f, "still life"),
+            Periodicity::Oscillator { period } => write!(f, "oscillator p{period}"),
+            Periodicity::Spaceship { period, dx, dy } => {
+                write!(f, "spaceship p{period} ({dx},{dy})")
+            }
+        }
+    }
+}
+
+// Translation invariant summary of a generation's cells and their states. The cell hashes are
+// combined with commutative operations so no sorting is needed.
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
+struct Signature {
+    population: usize,
+    width: i64,
+    height: i64,
+    sum: u64,
+    xor: u64,
+}
+
+// See: https://prng.di.unimi.it/splitmix64.c
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+fn signature<I>(cells: I) -> Option<(Signature, Bounds)>
+where
+    I: Iterator<Item = (GridCoord, u8)> + Clone,
+{
+    let mut bounds: Option<Bounds> = None;
+    let mut population = 0;
+    for (c, _) in cells.clone() {
+        if let GridCoord::Valid(x, y) = c {
+            match &mut bounds {
+                Some(b) => b.include(x, y),
+                None => {
+                    bounds = Some(Bounds {
+                        min_x: x,
+                        min_y: y,
+                        max_x: x,
+                        max_y: y,
+                    })
+                }
+            }
+            population += 1;
+        }
+    }
+    let bounds = bounds?;
+
+    let mut sum: u64 = 0;
+    let mut xor: u64 = 0;
+    for (c, state) in cells {
+        if let GridCoord::Valid(x, y) = c {
+            let nx = (x - bounds.min_x) as u64;
+            let ny = (y - bounds.min_y) as u64;
+            let mut h = mix(nx ^ ny.rotate_left(32));
+            // Dying cells of Generations rules block births, so their states are part of it.
+            if state != 1 {
+                h = mix(h ^ state as u64);
+            }
+            sum = sum.wrapping_add(h);
+            xor ^= mix(h);
+        }
+    }
+
+    Some((
+        Signature {
+            population,
+            width: bounds.width(),
+            height: bounds.height(),
+            sum,
+            xor,
+        },
+        bounds,
+    ))
+}
+
+/// Watches successive generations for a repeat of an earlier one.
+pub struct PeriodDetector {
+    max_period: usize,
+    // Signature -> (generation, top left) of its latest occurrence.
+    seen: HashMap<Signature, (usize, i64, i64)>,
+    history: VecDeque<(usize, Signature)>,
+    result: Option<Periodicity>,
+}
+
+impl PeriodDetector {
+    pub fn new() -> Self {
+        Self::with_max_period(DEFAULT_MAX_PERIOD)
+    }
+
+    pub fn with_max_period(max_period: usize) -> Self {
+        PeriodDetector {
+            max_period,
+            seen: HashMap::new(),
+            history: VecDeque::new(),
+            result: None,
+        }
+    }
+
+    /// Forget all history, e.g. after the universe has been edited.
+    pub fn reset(&mut self) {
+        self.seen.clear();
+        self.history.clear();
+        self.result = None;
+    }
+
+    /// The latest detected periodicity, if the pattern is currently repeating.
+    pub fn result(&self) -> Option<Periodicity> {
+        self.result
+    }
+
+    /// Record the universe's current generation, returning the periodicity if it repeats
+    /// an earlier one.
+    pub fn observe(&mut self, universe: &dyn Life) -> Option<Periodicity> {
+        if universe.rule().states() > 2 {
+            self.observe_states(universe.generation(), &universe.cell_states())
+        } else {
+            self.observe_cells(universe.generation(), &universe.live_cells())
+        }
+    }
+
+    pub fn observe_cells(&mut self, generation: usize, cells: &[GridCoord]) -> Option<Periodicity> {
+        self.observe_signature(generation, signature(cells.iter().map(|&c| (c, 1))))
+    }
+
+    /// As observe_cells, for the live and dying cells of a Generations rule with their states.
+    pub fn observe_states(
+        &mut self,
+        generation: usize,
+        cells: &[(GridCoord, u8)],
+    ) -> Option<Periodicity> {
+        // With no live cells there are no more births, whatever is still dying.
+        if !cells.iter().any(|&(_, state)| state == 1) {
+            self.result = Some(Periodicity::Empty);
+            return self.result;
+        }
+        self.observe_signature(generation, signature(cells.iter().copied()))
+    }
+
+    fn observe_signature(
+        &mut self,
+        generation: usize,
+        signature: Option<(Signature, Bounds)>,
+    ) -> Option<Periodicity> {
+        let Some((sig, bounds)) = signature else {
+            self.result = Some(Periodicity::Empty);
+            return self.result;
+        };
+
+        // Drop entries too old to be of interest, unless they have been seen again since.
+        while let Some(&(g, old)) = self.history.front() {
+            if g + self.max_period >= generation {
+                break;
+            }
+            self.history.pop_front();
+            if self
+                .seen
+                .get(&old)
+                .is_some_and(|&(latest, _, _)| latest == g)
+            {
+                self.seen.remove(&old);
+            }
+        }
+
+        self.result = match self.seen.get(&sig) {
+            Some(&(g, x, y)) if g < generation => {
+                let period = generation - g;
+                let (dx, dy) = (bounds.min_x - x, bounds.min_y - y);
+                Some(match (period, dx, dy) {
+                    (1, 0, 0) => Periodicity::StillLife,
+                    (period, 0, 0) => Periodicity::Oscillator { period },
+                    (period, dx, dy) => Periodicity::Spaceship { period, dx, dy },
+                })
+            }
+            _ => None,
+        };
+
+        self.seen
+            .insert(sig, (generation, bounds.min_x, bounds.min_y));
+        self.history.push_back((generation, sig));
+
+        self.result
+    }
+}
+
+impl Default for PeriodDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::grid::Universe;
+
+    fn run(cells: &[(i64, i64)], generations: usize) -> Option<Periodicity> {
+        let mut universe = Universe::new();
+        for &(x, y) in cells {
+            universe.grid.set(GridCoord::Valid(x, y));
+        }
+
+        let mut detector = PeriodDetector::new();
+        detector.observe(&universe);
+        for _ in 0..generations {
+            universe.update();
+            detector.observe(&universe);
+        }
+
+        detector.result()
+    }
+
+    #[test]
+    fn still_life() {
+        let block = [(0, 0), (1, 0), (0, 1), (1, 1)];
+        assert_eq!(run(&block, 1), Some(Periodicity::StillLife));
+    }
+
+    #[test]
+    fn oscillator() {
+        let blinker = [(0, 0), (0, 1), (0, 2)];
+        assert_eq!(run(&blinker, 1), None);
+        assert_eq!(
+            run(&blinker, 2),
+            Some(Periodicity::Oscillator { period: 2 })
+        );
+        assert_eq!(
+            run(&blinker, 5),
+            Some(Periodicity::Oscillator { period: 2 })
+        );
+    }
+
+    #[test]
+    fn spaceship() {
+        let glider = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+        assert_eq!(run(&glider, 3), None);
+        assert_eq!(
+            run(&glider, 4),
+            Some(Periodicity::Spaceship {
+                period: 4,
+                dx: 1,
+                dy: 1
+            })
+        );
+    }
+
+    #[test]
+    fn empty() {
+        let single = [(0, 0)];
+        assert_eq!(run(&single, 1), Some(Periodicity::Empty));
+    }
+
+    #[test]
+    fn brians_brain() -> anyhow::Result<()> {
+        let mut universe = Universe::with_rule("B2/S/C3".parse()?);
+        for (x, y) in [(0, 0), (0, 2), (1, 4)] {
+            universe.grid.set(GridCoord::Valid(x, y));
+        }
+
+        // The live cells of generation 6 are those of generation 4 moved one to the right,
+        // but the dying cells differ, and the pattern settles into a p4 spaceship instead.
+        let mut detector = PeriodDetector::new();
+        detector.observe(&universe);
+        for _ in 0..7 {
+            universe.update();
+            assert_eq!(detector.observe(&universe), None);
+        }
+        universe.update();
+        assert_eq!(
+            detector.observe(&universe),
+            Some(Periodicity::Spaceship {
+                period: 4,
+                dx: 1,
+                dy: -1
+            })
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn max_period() {
+        let mut detector = PeriodDetector::with_max_period(1);
+        let blinker_h = [
+            GridCoord::Valid(0, 0),
+            GridCoord::Valid(1, 0),
+            GridCoord::Valid(2, 0),
+        ];
+        let blinker_v = [
+            GridCoord::Valid(1, -1),
+            GridCoord::Valid(1, 0),
+            GridCoord::Valid(1, 1),
+        ];
+
+        detector.observe_cells(0, &blinker_h);
+        detector.observe_cells(1, &blinker_v);
+        assert_eq!(detector.observe_cells(2, &blinker_h), None);
+    }
+}
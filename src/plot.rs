@@ -0,0 +1,197 @@
+use ggez::glam::*;
+use ggez::graphics::{self, Color, DrawMode, DrawParam, Mesh, MeshBuilder, PxScale, Rect, Text};
+use ggez::{Context, GameResult};
+
+use boundlesse::stats::{History, Sample};
+
+// Generations shown, halved or doubled by zooming.
+const DEFAULT_SPAN: usize = 400;
+const MIN_SPAN: usize = 25;
+pub const MAX_SPAN: usize = 25_600;
+
+// A value plotted over time.
+struct Series {
+    name: &'static str,
+    value: fn(&Sample) -> f64,
+}
+
+fn ms(d: std::time::Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+// Each chart plots its series against a shared scale.
+const CHARTS: &[&[Series]] = &[
+    &[Series {
+        name: "Population",
+        value: |s| s.population as f64,
+    }],
+    &[
+        Series {
+            name: "Births",
+            value: |s| s.births as f64,
+        },
+        Series {
+            name: "Deaths",
+            value: |s| s.deaths as f64,
+        },
+    ],
+    &[
+        Series {
+            name: "Width",
+            value: |s| s.width as f64,
+        },
+        Series {
+            name: "Height",
+            value: |s| s.height as f64,
+        },
+    ],
+    &[
+        Series {
+            name: "Update ms",
+            value: |s| ms(s.update),
+        },
+        Series {
+            name: "Draw ms",
+            value: |s| ms(s.draw),
+        },
+    ],
+];
+
+pub struct PlotColors {
+    pub background: Color,
+    pub line: Color,
+    pub text: Color,
+    // One per series in a chart.
+    pub series: [Color; 2],
+}
+
+// Panel of charts of the recent history.
+pub struct Plot {
+    pub visible: bool,
+    span: usize,
+}
+
+impl Plot {
+    pub fn new() -> Self {
+        Plot {
+            visible: false,
+            span: DEFAULT_SPAN,
+        }
+    }
+
+    pub fn zoom_in(&mut self) {
+        self.span = (self.span / 2).max(MIN_SPAN);
+    }
+
+    pub fn zoom_out(&mut self) {
+        self.span = (self.span * 2).min(MAX_SPAN);
+    }
+
+    pub fn draw(
+        &self,
+        ctx: &mut Context,
+        canvas: &mut graphics::Canvas,
+        history: &History,
+        colors: &PlotColors,
+        font_size: f32,
+        window_size: (f32, f32),
+    ) -> GameResult {
+        let margin = 20.0;
+        let width = window_size.0 * 0.4;
+        let height = window_size.1 * 0.5;
+        let panel = Rect::new(
+            window_size.0 - width - margin,
+            window_size.1 - height - margin,
+            width,
+            height,
+        );
+
+        let mut background = colors.background;
+        background.a = 0.9;
+        let mut mb = MeshBuilder::new();
+        mb.rectangle(DrawMode::fill(), panel, background)?;
+        mb.rectangle(DrawMode::stroke(2.0), panel, colors.line)?;
+
+        let samples = history.samples();
+        let first = samples.len().saturating_sub(self.span);
+        let shown: Vec<&Sample> = samples.range(first..).collect();
+        let first_generation = shown.first().map_or(0, |s| s.generation);
+
+        // Plot at most one point per pixel.
+        let stride = (shown.len() as f32 / width).ceil().max(1.0) as usize;
+
+        let scale = PxScale::from(font_size * 0.5);
+        let chart_height = height / CHARTS.len() as f32;
+        let mut labels = vec![];
+        for (i, chart) in CHARTS.iter().enumerate() {
+            let area = Rect::new(
+                panel.x + margin,
+                panel.y + i as f32 * chart_height + scale.y + margin / 2.0,
+                panel.w - 2.0 * margin,
+                chart_height - scale.y - margin,
+            );
+            mb.line(
+                &[
+                    vec2(area.x, area.bottom()),
+                    vec2(area.right(), area.bottom()),
+                ],
+                1.0,
+                colors.line,
+            )?;
+
+            let max = chart
+                .iter()
+                .flat_map(|series| shown.iter().map(|s| (series.value)(s)))
+                .fold(0.0, f64::max);
+            let y_scale = if max > 0.0 { area.h as f64 / max } else { 0.0 };
+
+            let mut label = vec![];
+            for (series, color) in chart.iter().zip(colors.series) {
+                let points: Vec<Vec2> = shown
+                    .iter()
+                    .step_by(stride)
+                    .map(|s| {
+                        let x = (s.generation - first_generation) as f32 / self.span as f32;
+                        vec2(
+                            area.x + x * area.w,
+                            area.bottom() - ((series.value)(s) * y_scale) as f32,
+                        )
+                    })
+                    .collect();
+                if points.len() > 1 {
+                    mb.line(&points, 1.5, color)?;
+                }
+
+                let latest = shown.last().map_or(0.0, |s| (series.value)(s));
+                label.push(format!("{}: {}", series.name, format_value(latest)));
+            }
+            labels.push((
+                format!("{} (max {})", label.join(", "), format_value(max)),
+                vec2(area.x, area.y - scale.y),
+            ));
+        }
+
+        if let Some((label, _)) = labels.first_mut() {
+            label.push_str(&format!(", last {} generations", self.span));
+        }
+
+        canvas.draw(&Mesh::from_data(ctx, mb.build()), DrawParam::default());
+
+        for (label, at) in labels {
+            let mut text = Text::new(label);
+            text.set_scale(scale);
+            canvas.draw(&text, DrawParam::from(at).color(colors.text));
+        }
+
+        Ok(())
+    }
+}
+
+// Whole numbers without decimals, timings with them.
+fn format_value(v: f64) -> String {
+    if v.fract() == 0.0 {
+        format!("{}", v)
+    } else {
+        format!("{:.2}", v)
+    }
+}
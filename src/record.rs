@@ -0,0 +1,233 @@
+//! Recording a region of the universe over a range of generations as an animation.
+
+use anyhow::{anyhow, Result};
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::grid::{Bounds, GridCoord, Life};
+use crate::render::{render_cells, Style};
+
+/// How the frames are written.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Format {
+    /// A single animated GIF.
+    Gif,
+    /// Numbered PNG files alongside the given path, e.g. `gun-0000.png`, `gun-0001.png`...
+    PngSequence,
+}
+
+impl Format {
+    /// The format named by a file's extension, `.gif` or `.png`.
+    pub fn of(path: &str) -> Result<Self> {
+        match Path::new(path).extension().and_then(|e| e.to_str()) {
+            Some(e) if e.eq_ignore_ascii_case("gif") => Ok(Format::Gif),
+            Some(e) if e.eq_ignore_ascii_case("png") => Ok(Format::PngSequence),
+            _ => Err(anyhow!("Recordings must be .gif or .png files: {path}")),
+        }
+    }
+}
+
+/// What to record, and how to draw it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recording {
+    pub path: String,
+    /// Cells recorded, otherwise everything the pattern reaches while recording.
+    pub region: Option<Bounds>,
+    pub frames: usize,
+    /// Generations between frames.
+    pub step: usize,
+    pub style: Style,
+    /// Time each frame is shown in a GIF.
+    pub delay: Duration,
+}
+
+/// Captures frames as the universe runs, writing them out when finished.
+pub struct Recorder {
+    recording: Recording,
+    format: Format,
+    // Generation of the first frame.
+    start: Option<usize>,
+    // States of the universe's rule, for colouring dying cells.
+    states: u8,
+    frames: Vec<Vec<(GridCoord, u8)>>,
+}
+
+impl Recorder {
+    pub fn new(recording: Recording) -> Result<Self> {
+        if recording.frames == 0 || recording.step == 0 {
+            return Err(anyhow!("Recordings need at least one frame and step"));
+        }
+
+        Ok(Recorder {
+            format: Format::of(&recording.path)?,
+            recording,
+            start: None,
+            states: 2,
+            frames: vec![],
+        })
+    }
+
+    /// Capture the current generation if a frame is due, returning true once all are captured.
+    /// Frames are due every `step` generations, or at the next observed after that.
+    pub fn observe(&mut self, universe: &dyn Life) -> bool {
+        let generation = universe.generation();
+        let start = *self.start.get_or_insert(generation);
+        if !self.is_complete() && generation >= start + self.frames.len() * self.recording.step {
+            self.states = universe.rule().states();
+            self.frames.push(match &self.recording.region {
+                Some(region) => universe.states_in(region),
+                None => universe.cell_states(),
+            });
+        }
+
+        self.is_complete()
+    }
+
+    pub fn path(&self) -> &str {
+        &self.recording.path
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.frames.len() >= self.recording.frames
+    }
+
+    /// Frames captured so far, and in total.
+    pub fn progress(&self) -> (usize, usize) {
+        (self.frames.len(), self.recording.frames)
+    }
+
+    /// Draw and write the frames captured, returning how many were written.
+    pub fn finish(self) -> Result<usize> {
+        let region = match self.recording.region {
+            Some(region) => region,
+            None => {
+                let cells: Vec<GridCoord> =
+                    self.frames.concat().into_iter().map(|(c, _)| c).collect();
+                Bounds::of(&cells).ok_or(anyhow!("No cells to record"))?
+            }
+        };
+        let style = &self.recording.style;
+        let states = self.states;
+
+        match self.format {
+            Format::Gif => {
+                // Cells and the background are drawn in a few colours, indexed for the GIF.
+                let colors: Vec<[u8; 4]> = [Some(style.background), Some(style.cell), style.grid]
+                    .into_iter()
+                    .flatten()
+                    .chain((2..states).map(|state| style.color(state, states)))
+                    .collect();
+                let palette: Vec<u8> = colors.iter().flat_map(|c| [c[0], c[1], c[2]]).collect();
+
+                let mut encoder: Option<gif::Encoder<BufWriter<File>>> = None;
+                for cells in &self.frames {
+                    let image = render_cells(cells, &region, style, states)?;
+                    let encoder = match &mut encoder {
+                        Some(encoder) => encoder,
+                        None => {
+                            let (width, height) =
+                                (u16::try_from(image.width)?, u16::try_from(image.height)?);
+                            let out = BufWriter::new(File::create(&self.recording.path)?);
+                            let mut e = gif::Encoder::new(out, width, height, &palette)?;
+                            e.set_repeat(gif::Repeat::Infinite)?;
+                            encoder.insert(e)
+                        }
+                    };
+
+                    let indices: Vec<u8> = image
+                        .pixels
+                        .chunks_exact(4)
+                        .map(|p| colors.iter().position(|c| c == p).unwrap_or(0) as u8)
+                        .collect();
+                    let frame = gif::Frame {
+                        width: image.width as u16,
+                        height: image.height as u16,
+                        buffer: Cow::Owned(indices),
+                        // In hundredths of a second.
+                        delay: (self.recording.delay.as_millis() / 10).min(u16::MAX as u128) as u16,
+                        ..Default::default()
+                    };
+                    encoder.write_frame(&frame)?;
+                }
+            }
+            Format::PngSequence => {
+                let path = &self.recording.path;
+                let stem = &path[..path.len() - ".png".len()];
+                for (i, cells) in self.frames.iter().enumerate() {
+                    render_cells(cells, &region, style, states)?
+                        .save_png(&format!("{stem}-{i:04}.png"))?;
+                }
+            }
+        }
+
+        Ok(self.frames.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::Universe;
+
+    #[test]
+    fn record_glider() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("boundlesse-record-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+
+        let glider = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+        let recording = Recording {
+            path: path("glider.gif"),
+            region: None,
+            frames: 3,
+            step: 4,
+            style: Style {
+                cell_size: 2.0,
+                ..Style::default()
+            },
+            delay: Duration::from_millis(100),
+        };
+
+        let mut universe = Universe::new();
+        for (x, y) in glider {
+            universe.set(GridCoord::Valid(x, y));
+        }
+        let mut recorder = Recorder::new(recording.clone())?;
+        while !recorder.observe(&universe) {
+            universe.update();
+        }
+        assert_eq!(universe.generation(), 8);
+        assert_eq!(recorder.finish()?, 3);
+
+        // The glider moves 2 cells each way over the recording, so the frames are 5 cells square.
+        let gif = std::fs::read(path("glider.gif"))?;
+        assert_eq!(&gif[..6], b"GIF89a");
+        assert_eq!((gif[6], gif[8]), (10, 10));
+
+        // The same as numbered PNG files.
+        let mut universe = Universe::new();
+        for (x, y) in glider {
+            universe.set(GridCoord::Valid(x, y));
+        }
+        let mut recorder = Recorder::new(Recording {
+            path: path("glider.png"),
+            ..recording
+        })?;
+        while !recorder.observe(&universe) {
+            universe.update();
+        }
+        recorder.finish()?;
+        for i in 0..3 {
+            assert!(dir.join(format!("glider-{i:04}.png")).exists());
+        }
+        assert!(!dir.join("glider-0003.png").exists());
+
+        std::fs::remove_dir_all(&dir)?;
+        assert!(Format::of("glider.rle").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,242 @@
+//! Rendering the universe to images in software, for export without a window.
+
+use anyhow::{anyhow, Result};
+use std::fs::File;
+use std::io::BufWriter;
+
+use crate::grid::{Bounds, GridCoord, Life};
+
+// Images are limited to this many pixels, 256MB of RGBA.
+const MAX_PIXELS: usize = 1 << 26;
+
+// Grid lines are only drawn between cells at least this many pixels across.
+const MIN_GRID_CELL_SIZE: f64 = 4.0;
+
+/// How cells are drawn into an image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Style {
+    /// Pixels per cell, below 1 to draw several cells in a pixel.
+    pub cell_size: f64,
+    pub background: [u8; 4],
+    pub cell: [u8; 4],
+    /// Colours of dying cells under Generations rules, spread from the first dying state to the
+    /// last.
+    pub dying: Vec<[u8; 4]>,
+    /// Colour of the lines between cells, if drawn.
+    pub grid: Option<[u8; 4]>,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style {
+            cell_size: 8.0,
+            background: [255, 255, 255, 255],
+            cell: [0, 0, 0, 255],
+            dying: vec![[48, 80, 208, 255], [200, 208, 240, 255]],
+            grid: None,
+        }
+    }
+}
+
+impl Style {
+    /// The colour of a cell in a state, of a rule with that many states.
+    pub fn color(&self, state: u8, states: u8) -> [u8; 4] {
+        if state <= 1 || self.dying.is_empty() {
+            return self.cell;
+        }
+
+        let t = if states > 3 {
+            (state - 2) as f32 / (states - 3) as f32 * (self.dying.len() - 1) as f32
+        } else {
+            0.0
+        };
+        let i = (t as usize).min(self.dying.len().saturating_sub(2));
+        match self.dying.get(i + 1) {
+            Some(next) => {
+                let t = (t - i as f32).min(1.0);
+                let a = self.dying[i];
+                [0, 1, 2, 3]
+                    .map(|c| (a[c] as f32 + (next[c] as f32 - a[c] as f32) * t).round() as u8)
+            }
+            None => self.dying[i],
+        }
+    }
+}
+
+/// RGBA pixels, row by row.
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize, background: [u8; 4]) -> Result<Self> {
+        if width.saturating_mul(height) > MAX_PIXELS {
+            return Err(anyhow!(
+                "Image of {width}x{height} pixels is too large, try a smaller cell size"
+            ));
+        }
+
+        Ok(Image {
+            width,
+            height,
+            pixels: background.repeat(width * height),
+        })
+    }
+
+    /// Fill the pixels from (x0, y0) up to but not including (x1, y1).
+    pub fn fill(&mut self, (x0, y0): (usize, usize), (x1, y1): (usize, usize), color: [u8; 4]) {
+        let (x1, y1) = (x1.min(self.width), y1.min(self.height));
+        for y in y0..y1 {
+            let row = &mut self.pixels[(y * self.width + x0) * 4..(y * self.width + x1) * 4];
+            for pixel in row.chunks_exact_mut(4) {
+                pixel.copy_from_slice(&color);
+            }
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 4] {
+        let i = (y * self.width + x) * 4;
+        [
+            self.pixels[i],
+            self.pixels[i + 1],
+            self.pixels[i + 2],
+            self.pixels[i + 3],
+        ]
+    }
+
+    pub fn save_png(&self, filename: &str) -> Result<()> {
+        let out = BufWriter::new(File::create(filename)?);
+        let mut encoder = png::Encoder::new(out, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        writer.finish()?;
+
+        Ok(())
+    }
+}
+
+/// Draw the cells within the bounds, each cell_size pixels across.
+pub fn render(universe: &dyn Life, bounds: &Bounds, style: &Style) -> Result<Image> {
+    render_cells(
+        &universe.states_in(bounds),
+        bounds,
+        style,
+        universe.rule().states(),
+    )
+}
+
+/// Draw those of the cells within the bounds, in the colours of their states of a rule with that
+/// many states, each cell_size pixels across.
+pub fn render_cells(
+    cells: &[(GridCoord, u8)],
+    bounds: &Bounds,
+    style: &Style,
+    states: u8,
+) -> Result<Image> {
+    if style.cell_size.is_nan() || style.cell_size <= 0.0 {
+        return Err(anyhow!("Cell size must be positive"));
+    }
+
+    let cs = style.cell_size;
+    // Pixel edges of the cells at offsets from the bounds, at least a pixel apart.
+    let edge = |offset: i64| (offset as f64 * cs).floor() as usize;
+    let span = |offset: i64| (edge(offset), edge(offset + 1).max(edge(offset) + 1));
+
+    let (width, height) = (span(bounds.width() - 1).1, span(bounds.height() - 1).1);
+    let mut image = Image::new(width, height, style.background)?;
+
+    for &(c, state) in cells {
+        if let GridCoord::Valid(x, y) = c {
+            if !bounds.contains(x, y) || state == 0 {
+                continue;
+            }
+            let (x0, x1) = span(x - bounds.min_x);
+            let (y0, y1) = span(y - bounds.min_y);
+            image.fill((x0, y0), (x1, y1), style.color(state, states));
+        }
+    }
+
+    if let Some(grid) = style.grid.filter(|_| cs >= MIN_GRID_CELL_SIZE) {
+        for x in 0..=bounds.width() {
+            let x0 = edge(x).min(width - 1);
+            image.fill((x0, 0), (x0 + 1, height), grid);
+        }
+        for y in 0..=bounds.height() {
+            let y0 = edge(y).min(height - 1);
+            image.fill((0, y0), (width, y0 + 1), grid);
+        }
+    }
+
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::Universe;
+
+    #[test]
+    fn render_cells() -> Result<()> {
+        let mut universe = Universe::new();
+        for (x, y) in [(0, 0), (2, 1), (-5, -5)] {
+            universe.set(GridCoord::Valid(x, y));
+        }
+        let bounds = Bounds {
+            min_x: 0,
+            min_y: 0,
+            max_x: 3,
+            max_y: 1,
+        };
+        let style = Style::default();
+
+        let image = render(&universe, &bounds, &style)?;
+        assert_eq!((image.width, image.height), (32, 16));
+        assert_eq!(image.pixel(7, 7), style.cell);
+        assert_eq!(image.pixel(8, 7), style.background);
+        assert_eq!(image.pixel(16, 8), style.cell);
+        assert_eq!(image.pixel(16, 7), style.background);
+
+        // Shrunk, cells share pixels and never vanish.
+        let small = Style {
+            cell_size: 0.25,
+            ..style.clone()
+        };
+        let image = render(&universe, &bounds, &small)?;
+        assert_eq!((image.width, image.height), (1, 1));
+        assert_eq!(image.pixel(0, 0), style.cell);
+
+        // Grid lines between the cells, including the far edges.
+        let grid = [128, 128, 128, 255];
+        let lined = Style {
+            grid: Some(grid),
+            ..style.clone()
+        };
+        let image = render(&universe, &bounds, &lined)?;
+        assert_eq!(image.pixel(8, 3), grid);
+        assert_eq!(image.pixel(31, 3), grid);
+        assert_eq!(image.pixel(4, 4), style.cell);
+
+        let huge = Style {
+            cell_size: 1e6,
+            ..style.clone()
+        };
+        assert!(render(&universe, &bounds, &huge).is_err());
+
+        // Dying cells fade through the dying colours.
+        let cells = [
+            (GridCoord::Valid(0, 0), 2),
+            (GridCoord::Valid(1, 0), 4),
+            (GridCoord::Valid(2, 0), 3),
+        ];
+        let image = super::render_cells(&cells, &bounds, &style, 5)?;
+        assert_eq!(image.pixel(4, 4), style.dying[0]);
+        assert_eq!(image.pixel(12, 4), style.dying[1]);
+        assert_eq!(image.pixel(20, 4), [124, 144, 224, 255]);
+
+        Ok(())
+    }
+}
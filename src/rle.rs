@@ -1,20 +1,87 @@
+//! Reading and writing RLE encoded patterns (see: https://conwaylife.com/wiki/Run_Length_Encoded),
+//! including the multi-state form for Generations rules.
+
 use anyhow::{anyhow, Result};
 use log::*;
 use regex::Regex;
 use std::cmp::max;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+
+use crate::grid::{Backend, Bounds, GridCoord, Life};
+use crate::rule::Rule;
 
-use crate::grid::GridCoord;
+// Maximum line length when writing RLE data.
+const MAX_LINE: usize = 70;
 
+/// Receives the cells of a pattern as it is loaded, with their states: 0 dead, 1 alive or from
+/// 2 dying.
 pub trait Inject {
-    fn inject(&mut self, coord: GridCoord, alive: bool) -> anyhow::Result<()>;
+    /// The rule named in the pattern's header, given before any of its cells.
+    fn rule(&mut self, _rule: Rule) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn inject(&mut self, coord: GridCoord, state: u8) -> anyhow::Result<()>;
+}
+
+/// Inject a pattern into a universe, starting it afresh under the pattern's rule unless a rule
+/// was given.
+pub struct Injector<'a> {
+    universe: &'a mut Box<dyn Life>,
+    backend: Backend,
+    // The rule given, kept whatever the pattern names.
+    rule: Option<Rule>,
+    pub cells: usize,
+}
+
+impl<'a> Injector<'a> {
+    pub fn new(universe: &'a mut Box<dyn Life>, backend: Backend, rule: Option<Rule>) -> Self {
+        Self {
+            universe,
+            backend,
+            rule,
+            cells: 0,
+        }
+    }
+}
+
+impl<'a> Inject for Injector<'a> {
+    fn rule(&mut self, rule: Rule) -> anyhow::Result<()> {
+        match self.rule {
+            Some(given) if given != rule => {
+                warn!(
+                    "Pattern rule {} differs from the rule given, {}",
+                    rule, given
+                )
+            }
+            Some(_) => {}
+            None if rule != self.universe.rule() => {
+                info!("Using the pattern's rule: {}", rule);
+                *self.universe = self.backend.create(rule);
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    fn inject(&mut self, coord: GridCoord, state: u8) -> anyhow::Result<()> {
+        if state > 0 {
+            self.universe.set_state(coord, state);
+            trace!("Setting coord: {:?} {}", coord, state);
+            self.cells += 1;
+        } else {
+            self.universe.unset(coord);
+            trace!("Unsetting coord: {:?}", coord);
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
 enum RLEToken {
     Dead(u32),
-    Alive(u32),
+    Cells(u32, u8),
     Eol(u32),
     Eof,
 }
@@ -22,7 +89,7 @@ enum RLEToken {
 #[derive(Debug)]
 enum RLELine {
     Comment(String),
-    Header(usize, usize),
+    Header(usize, usize, Option<String>),
     Data(Vec<RLEToken>),
 }
 
@@ -44,9 +111,13 @@ fn parse_header(header: &str) -> Result<RLELine> {
     let x = captures.get(1).ok_or(anyhow!("Header missing x"))?;
     let y = captures.get(2).ok_or(anyhow!("Header missing y"))?;
 
+    let re: Regex = Regex::new(r",[ ]*rule[ ]*=[ ]*([^, ]+)")?;
+    let rule = re.captures(header).map(|c| c[1].to_owned());
+
     Ok(RLELine::Header(
         x.as_str().parse::<usize>()?,
         y.as_str().parse::<usize>()?,
+        rule,
     ))
 }
 
@@ -58,10 +129,15 @@ fn parse_data(line: &str) -> Result<RLELine> {
     for c in line.chars() {
         match c {
             'o' => {
-                tokens.push(RLEToken::Alive(max(count, 1)));
+                tokens.push(RLEToken::Cells(max(count, 1), 1));
+                count = 0;
+            }
+            // Multi-state patterns name the states beyond dead A, B...
+            'A'..='X' => {
+                tokens.push(RLEToken::Cells(max(count, 1), c as u8 - b'A' + 1));
                 count = 0;
             }
-            'b' => {
+            'b' | '.' => {
                 tokens.push(RLEToken::Dead(max(count, 1)));
                 count = 0;
             }
@@ -95,6 +171,8 @@ fn parse_line(line: &str) -> Result<RLELine> {
     }
 }
 
+/// Load an RLE pattern, centered on 0,0. Dead cells are only injected if skip_blank is false.
+/// The rule in the header, if any, is passed on before the cells.
 #[allow(unused)]
 pub fn load_rle(filename: &str, inject: &mut impl Inject, skip_blank: bool) -> anyhow::Result<()> {
     let lines = read_lines(filename)?;
@@ -122,9 +200,15 @@ pub fn load_rle(filename: &str, inject: &mut impl Inject, skip_blank: bool) -> a
     let max_y: i64;
 
     match lines.next() {
-        Some(RLELine::Header(hx, hy)) => {
+        Some(RLELine::Header(hx, hy, rule)) => {
             max_x = *hx as i64;
             max_y = *hy as i64;
+            if let Some(rule) = rule {
+                match rule.parse() {
+                    Ok(rule) => inject.rule(rule)?,
+                    Err(e) => warn!("Ignoring the pattern's rule {}: {}", rule, e),
+                }
+            }
         }
         Some(_) => return Err(anyhow!("Missing header")),
         _ => return Err(anyhow!("Unexpected EOF reading header")),
@@ -139,7 +223,7 @@ pub fn load_rle(filename: &str, inject: &mut impl Inject, skip_blank: bool) -> a
     for dl in lines {
         match dl {
             RLELine::Comment(_) => return Err(anyhow!("Comment found in data")),
-            RLELine::Header(_, _) => return Err(anyhow!("Header found in data")),
+            RLELine::Header(..) => return Err(anyhow!("Header found in data")),
             RLELine::Data(tokens) => {
                 for t in tokens {
                     match t {
@@ -148,27 +232,21 @@ pub fn load_rle(filename: &str, inject: &mut impl Inject, skip_blank: bool) -> a
                                 x += *c as i64;
                             } else {
                                 for _ in 0..*c {
-                                    inject.inject(
-                                        GridCoord::Valid(x + offset_x, y + offset_y),
-                                        false,
-                                    );
+                                    inject.inject(GridCoord::Valid(x + offset_x, y + offset_y), 0);
                                     x += 1;
                                 }
                             }
                         }
-                        RLEToken::Alive(c) => {
+                        RLEToken::Cells(c, state) => {
                             for _ in 0..*c {
-                                inject.inject(GridCoord::Valid(x + offset_x, y + offset_y), true);
+                                inject.inject(GridCoord::Valid(x + offset_x, y + offset_y), *state);
                                 x += 1;
                             }
                         }
                         RLEToken::Eol(c) => {
                             if !skip_blank {
                                 for x in x..max_x {
-                                    inject.inject(
-                                        GridCoord::Valid(x + offset_x, y + offset_y),
-                                        false,
-                                    );
+                                    inject.inject(GridCoord::Valid(x + offset_x, y + offset_y), 0);
                                 }
                             }
 
@@ -181,7 +259,7 @@ pub fn load_rle(filename: &str, inject: &mut impl Inject, skip_blank: bool) -> a
                                     for i in 0..max_x {
                                         inject.inject(
                                             GridCoord::Valid(i + offset_x, y + offset_y),
-                                            false,
+                                            0,
                                         );
                                     }
                                 }
@@ -192,10 +270,7 @@ pub fn load_rle(filename: &str, inject: &mut impl Inject, skip_blank: bool) -> a
                         RLEToken::Eof => {
                             if !skip_blank {
                                 for x in x..max_x {
-                                    inject.inject(
-                                        GridCoord::Valid(x + offset_x, y + offset_y),
-                                        false,
-                                    );
+                                    inject.inject(GridCoord::Valid(x + offset_x, y + offset_y), 0);
                                 }
                             }
 
@@ -214,6 +289,118 @@ pub fn load_rle(filename: &str, inject: &mut impl Inject, skip_blank: bool) -> a
     Ok(())
 }
 
+// Accumulates RLE tokens into lines of at most MAX_LINE characters.
+struct RLEWriter<'a, W: Write> {
+    out: &'a mut W,
+    line: String,
+}
+
+impl<'a, W: Write> RLEWriter<'a, W> {
+    fn token(&mut self, count: i64, tag: char) -> Result<()> {
+        let token = if count > 1 {
+            format!("{count}{tag}")
+        } else {
+            tag.to_string()
+        };
+
+        if self.line.len() + token.len() > MAX_LINE {
+            writeln!(self.out, "{}", self.line)?;
+            self.line.clear();
+        }
+        self.line.push_str(&token);
+
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<()> {
+        self.token(1, '!')?;
+        writeln!(self.out, "{}", self.line)?;
+        Ok(())
+    }
+}
+
+/// Write the live cells as RLE, with the top left of the bounding box as the pattern origin.
+pub fn write_rle(out: &mut impl Write, cells: &[GridCoord], rule: &Rule) -> Result<()> {
+    let cells: Vec<(GridCoord, u8)> = cells.iter().map(|&c| (c, 1)).collect();
+    write_rle_states(out, &cells, rule)
+}
+
+/// Write cells with their states as RLE, naming the states with letters for Generations rules.
+pub fn write_rle_states(
+    out: &mut impl Write,
+    cells: &[(GridCoord, u8)],
+    rule: &Rule,
+) -> Result<()> {
+    let mut rows: Vec<(i64, i64, u8)> = cells
+        .iter()
+        .filter_map(|&(c, state)| match c {
+            GridCoord::Valid(x, y) if state > 0 => Some((y, x, state)),
+            _ => None,
+        })
+        .collect();
+    rows.sort();
+
+    let coords: Vec<GridCoord> = rows
+        .iter()
+        .map(|&(y, x, _)| GridCoord::Valid(x, y))
+        .collect();
+    let (min_x, min_y, width, height) = match Bounds::of(&coords) {
+        Some(b) => (b.min_x, b.min_y, b.width(), b.height()),
+        None => (0, 0, 0, 0),
+    };
+    writeln!(out, "x = {width}, y = {height}, rule = {rule}")?;
+
+    let multi_state = rule.states() > 2;
+    let (dead, tag) = if multi_state { ('.', 'A') } else { ('b', 'o') };
+    let tag = |state: u8| (tag as u8 + state - 1) as char;
+
+    let mut writer = RLEWriter {
+        out,
+        line: String::new(),
+    };
+
+    // The state and length of the run of cells being written.
+    let (mut cx, mut cy) = (min_x, min_y);
+    let mut run: (u8, i64) = (0, 0);
+    for (y, x, state) in rows {
+        if run.1 > 0 && (y > cy || x > cx || state != run.0) {
+            writer.token(run.1, tag(run.0))?;
+            run.1 = 0;
+        }
+        if y > cy {
+            writer.token(y - cy, '$')?;
+            cx = min_x;
+            cy = y;
+        }
+        if x > cx {
+            writer.token(x - cx, dead)?;
+        }
+
+        run = (state, run.1 + 1);
+        cx = x + 1;
+    }
+    if run.1 > 0 {
+        writer.token(run.1, tag(run.0))?;
+    }
+
+    writer.finish()
+}
+
+/// Write the live cells to an RLE file.
+pub fn save_rle(filename: &str, cells: &[GridCoord], rule: &Rule) -> Result<()> {
+    let cells: Vec<(GridCoord, u8)> = cells.iter().map(|&c| (c, 1)).collect();
+    save_rle_states(filename, &cells, rule)
+}
+
+/// Write cells with their states to an RLE file.
+pub fn save_rle_states(filename: &str, cells: &[(GridCoord, u8)], rule: &Rule) -> Result<()> {
+    let mut out = BufWriter::new(File::create(filename)?);
+    write_rle_states(&mut out, cells, rule)?;
+    out.flush()?;
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -226,7 +413,7 @@ mod tests {
 
     struct TestData {
         pub injects: usize,
-        pub coords: HashMap<GridCoord, bool>,
+        pub coords: HashMap<GridCoord, u8>,
     }
 
     impl TestData {
@@ -239,10 +426,10 @@ mod tests {
     }
 
     impl Inject for TestData {
-        fn inject(&mut self, coord: GridCoord, alive: bool) -> anyhow::Result<()> {
+        fn inject(&mut self, coord: GridCoord, state: u8) -> anyhow::Result<()> {
             self.injects += 1;
-            if alive {
-                self.coords.insert(coord, alive);
+            if state > 0 {
+                self.coords.insert(coord, state);
             }
 
             Ok(())
@@ -258,10 +445,7 @@ mod tests {
 
         assert_eq!(data.injects, 1);
         assert_eq!(hm.len(), 1);
-        assert_eq!(
-            *hm.get(&GridCoord::Valid(0, 0)).expect("Missing cell"),
-            true
-        );
+        assert_eq!(*hm.get(&GridCoord::Valid(0, 0)).expect("Missing cell"), 1);
 
         Ok(())
     }
@@ -277,4 +461,135 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn save_load() -> Result<()> {
+        init();
+
+        // Glider, offset from the origin.
+        let cells = [
+            GridCoord::Valid(11, 20),
+            GridCoord::Valid(12, 21),
+            GridCoord::Valid(10, 22),
+            GridCoord::Valid(11, 22),
+            GridCoord::Valid(12, 22),
+        ];
+
+        let mut out: Vec<u8> = vec![];
+        write_rle(&mut out, &cells, &Rule::default())?;
+        assert_eq!(
+            String::from_utf8(out)?,
+            "x = 3, y = 3, rule = B3/S23\nbo$2bo$3o!\n"
+        );
+
+        let filename = std::env::temp_dir().join("boundlesse-save-load.rle");
+        let filename = filename.to_str().expect("Temp path");
+        save_rle(filename, &cells, &Rule::default())?;
+
+        let mut data = TestData::new();
+        load_rle(filename, &mut data, true)?;
+
+        // Loading centers the pattern on 0,0.
+        let mut loaded: Vec<GridCoord> = data.coords.keys().copied().collect();
+        loaded.sort();
+        let mut expected: Vec<GridCoord> = cells.iter().map(|c| c.adjust(-11, -21)).collect();
+        expected.sort();
+        assert_eq!(loaded, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn save_load_states() -> Result<()> {
+        // Brian's Brain cells, dying cells behind the live ones.
+        let cells = [
+            (GridCoord::Valid(0, 0), 2),
+            (GridCoord::Valid(1, 0), 1),
+            (GridCoord::Valid(0, 1), 2),
+            (GridCoord::Valid(1, 1), 1),
+            (GridCoord::Valid(4, 1), 1),
+            (GridCoord::Valid(5, 1), 1),
+        ];
+        let rule: Rule = "B2/S/C3".parse()?;
+
+        let mut out: Vec<u8> = vec![];
+        write_rle_states(&mut out, &cells, &rule)?;
+        assert_eq!(
+            String::from_utf8(out)?,
+            "x = 6, y = 2, rule = B2/S/C3\nBA$BA2.2A!\n"
+        );
+
+        let filename = std::env::temp_dir().join("boundlesse-save-load-states.rle");
+        let filename = filename.to_str().expect("Temp path");
+        save_rle_states(filename, &cells, &rule)?;
+
+        let mut data = TestData::new();
+        load_rle(filename, &mut data, true)?;
+        let mut loaded: Vec<(GridCoord, u8)> = data.coords.into_iter().collect();
+        loaded.sort();
+        let mut expected: Vec<(GridCoord, u8)> = cells
+            .iter()
+            .map(|&(c, state)| (c.adjust(-3, -1), state))
+            .collect();
+        expected.sort();
+        assert_eq!(loaded, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn load_pattern_rule() -> Result<()> {
+        let rule: Rule = "B2/S/C3".parse()?;
+        let mut universe = Backend::Generations.create(rule);
+        universe.set(GridCoord::Valid(0, 0));
+        universe.set(GridCoord::Valid(1, 0));
+        universe.set(GridCoord::Valid(0, 1));
+        universe.update();
+        let cells = universe.cell_states();
+        assert!(cells.iter().any(|&(_, state)| state == 2));
+
+        let filename = std::env::temp_dir().join("boundlesse-load-pattern-rule.rle");
+        let filename = filename.to_str().expect("Temp path");
+        save_rle_states(filename, &cells, &rule)?;
+
+        // Without a rule given, the pattern's own rule keeps its dying cells.
+        let mut loaded = Backend::Generations.create(Rule::default());
+        load_rle(
+            filename,
+            &mut Injector::new(&mut loaded, Backend::Generations, None),
+            true,
+        )?;
+        assert_eq!(loaded.rule(), rule);
+        let mut states: Vec<u8> = loaded.cell_states().iter().map(|&(_, s)| s).collect();
+        let mut expected: Vec<u8> = cells.iter().map(|&(_, s)| s).collect();
+        states.sort();
+        expected.sort();
+        assert_eq!(states, expected);
+
+        // A rule given wins over the pattern's.
+        let mut loaded = Backend::Generations.create(Rule::default());
+        let given = Some(Rule::default());
+        load_rle(
+            filename,
+            &mut Injector::new(&mut loaded, Backend::Generations, given),
+            true,
+        )?;
+        assert_eq!(loaded.rule(), Rule::default());
+
+        Ok(())
+    }
+
+    #[test]
+    fn save_wraps_lines() -> Result<()> {
+        let cells: Vec<GridCoord> = (0..200).map(|x| GridCoord::Valid(x * 2, 0)).collect();
+
+        let mut out: Vec<u8> = vec![];
+        write_rle(&mut out, &cells, &Rule::default())?;
+
+        let text = String::from_utf8(out)?;
+        assert!(text.lines().all(|l| l.len() <= MAX_LINE));
+        assert!(text.trim_end().ends_with("o!"));
+
+        Ok(())
+    }
 }
@@ -0,0 +1,201 @@
+//! Life-like rules in B/S notation, and Generations rules with dying states in B/S/C notation.
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Most states a cell can have, as RLE names the states beyond dead with the letters A to X.
+pub const MAX_STATES: u8 = 25;
+
+/// Life-like (outer totalistic) rule, e.g. B3/S23, or a Generations rule, e.g. B2/S/C3.
+///
+/// In a Generations rule live cells which don't survive pass through dying states, one a
+/// generation, before they are dead. Dying cells don't count as live neighbours and can't be
+/// born.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Rule {
+    // Indexed by number of live neighbours.
+    birth: [bool; 9],
+    survive: [bool; 9],
+    // Dead, alive and any dying states, so 2 for Life-like rules.
+    states: u8,
+}
+
+impl Rule {
+    /// Number of cell states: 0 is dead, 1 alive and those from 2 dying.
+    pub fn states(&self) -> u8 {
+        self.states
+    }
+
+    /// The state of a cell in `state` after a generation with this many live neighbours.
+    pub fn next(&self, state: u8, neighbours: usize) -> u8 {
+        match state {
+            0 if self.born(neighbours) => 1,
+            0 => 0,
+            1 if self.survives(neighbours) => 1,
+            state => (state + 1) % self.states,
+        }
+    }
+
+    /// Whether a dead cell with this many live neighbours is born.
+    pub fn born(&self, neighbours: usize) -> bool {
+        self.birth[neighbours]
+    }
+
+    /// Whether a live cell with this many live neighbours survives.
+    pub fn survives(&self, neighbours: usize) -> bool {
+        self.survive[neighbours]
+    }
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        // Conway's Life.
+        "B3/S23".parse().expect("Valid default rule")
+    }
+}
+
+fn parse_counts(counts: &str) -> Result<[bool; 9]> {
+    let mut result = [false; 9];
+    for c in counts.chars() {
+        let n = c
+            .to_digit(10)
+            .filter(|&n| n <= 8)
+            .ok_or(anyhow!("Invalid neighbour count: {c}"))?;
+        result[n as usize] = true;
+    }
+
+    Ok(result)
+}
+
+impl FromStr for Rule {
+    type Err = anyhow::Error;
+
+    // Accepts B/S notation (B3/S23) and the older S/B notation (23/3), each optionally followed
+    // by the number of states for Generations rules (B2/S/C3 or /2/3).
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim().to_ascii_uppercase();
+        let (b, s) = s.split_once('/').ok_or(anyhow!("Rule missing '/'"))?;
+        let (s, c) = match s.split_once('/') {
+            Some((s, c)) => (s, Some(c)),
+            None => (s, None),
+        };
+
+        let (birth, survive, states) = match (b.strip_prefix('B'), s.strip_prefix('S')) {
+            (Some(b), Some(s)) => (b, s, c.map(|c| c.strip_prefix('C').unwrap_or(c))),
+            (None, None) => (s, b, c),
+            _ => return Err(anyhow!("Malformed rule: {b}/{s}")),
+        };
+        let states = match states {
+            Some(c) => c
+                .parse::<u8>()
+                .ok()
+                .filter(|n| (2..=MAX_STATES).contains(n))
+                .ok_or(anyhow!(
+                    "Invalid number of states: {c}, expected 2 to {MAX_STATES}"
+                ))?,
+            None => 2,
+        };
+
+        let rule = Rule {
+            birth: parse_counts(birth)?,
+            survive: parse_counts(survive)?,
+            states,
+        };
+
+        // Cells with no live neighbours are never visited, so B0 cannot be simulated.
+        if rule.birth[0] {
+            return Err(anyhow!("B0 rules are not supported"));
+        }
+
+        Ok(rule)
+    }
+}
+
+impl TryFrom<String> for Rule {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<Rule> for String {
+    fn from(rule: Rule) -> Self {
+        rule.to_string()
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let counts = |v: &[bool; 9]| -> String {
+            (0..9)
+                .filter(|&n| v[n])
+                .map(|n| char::from_digit(n as u32, 10).unwrap())
+                .collect()
+        };
+        write!(f, "B{}/S{}", counts(&self.birth), counts(&self.survive))?;
+        if self.states > 2 {
+            write!(f, "/C{}", self.states)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_life() -> Result<()> {
+        let rule: Rule = "B3/S23".parse()?;
+
+        assert_eq!(rule, Rule::default());
+        assert!(rule.born(3));
+        assert!(!rule.born(2));
+        assert!(rule.survives(2));
+        assert!(rule.survives(3));
+        assert!(!rule.survives(4));
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_variants() -> Result<()> {
+        assert_eq!("b3/s23".parse::<Rule>()?, Rule::default());
+        assert_eq!("23/3".parse::<Rule>()?, Rule::default());
+        assert_eq!("B36/S23".parse::<Rule>()?.to_string(), "B36/S23");
+        assert_eq!("B2/S".parse::<Rule>()?.to_string(), "B2/S");
+
+        assert!("B3S23".parse::<Rule>().is_err());
+        assert!("B9/S23".parse::<Rule>().is_err());
+        assert!("B03/S23".parse::<Rule>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_generations() -> Result<()> {
+        let brain: Rule = "B2/S/C3".parse()?;
+        assert_eq!(brain.states(), 3);
+        assert_eq!(brain.to_string(), "B2/S/C3");
+        assert_eq!("/2/3".parse::<Rule>()?, brain);
+        assert_eq!("345/2/4".parse::<Rule>()?.to_string(), "B2/S345/C4");
+        assert_eq!("B3/S23/C2".parse::<Rule>()?, Rule::default());
+
+        assert!("B2/S/C1".parse::<Rule>().is_err());
+        assert!("B2/S/C26".parse::<Rule>().is_err());
+        assert!("B2/S/CX".parse::<Rule>().is_err());
+
+        // Live cells which don't survive start dying, and dying cells can't be born.
+        assert_eq!(brain.next(0, 2), 1);
+        assert_eq!(brain.next(1, 2), 2);
+        assert_eq!(brain.next(2, 2), 0);
+        let life = Rule::default();
+        assert_eq!((life.next(1, 1), life.next(1, 2)), (0, 1));
+
+        Ok(())
+    }
+}
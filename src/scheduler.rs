@@ -0,0 +1,308 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::settings::{Speed, MAX_STEP_EXPONENT};
+
+// Achieved rates are measured over this much recent time.
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+// Generations further behind schedule than this are dropped rather than caught up, so a slow
+// pattern runs at its own pace instead of stalling the UI.
+const MAX_LAG: Duration = Duration::from_millis(100);
+
+// Counts of events over the last RATE_WINDOW.
+#[derive(Debug, Default)]
+struct Rate {
+    events: VecDeque<(Instant, u64)>,
+}
+
+impl Rate {
+    fn add(&mut self, now: Instant, n: u64) {
+        self.events.push_back((now, n));
+        while self
+            .events
+            .front()
+            .is_some_and(|&(t, _)| now - t > RATE_WINDOW)
+        {
+            self.events.pop_front();
+        }
+    }
+
+    // Events per second since the first in the window, which only marks the start.
+    fn per_second(&self, now: Instant) -> f64 {
+        match self.events.front() {
+            Some(&(start, _)) if now - start <= RATE_WINDOW && self.events.len() > 1 => {
+                let (end, _) = self.events[self.events.len() - 1];
+                let n: u64 = self.events.iter().skip(1).map(|&(_, n)| n).sum();
+                n as f64 / (end - start).as_secs_f64().max(1e-6)
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+// Generations to compute in one go: up to `count`, or until the deadline passes once at least
+// one is done.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Budget {
+    pub count: u64,
+    pub deadline: Option<Instant>,
+}
+
+// When generations are computed and frames drawn, separately, and how often each actually is.
+// Generations are scheduled in steps of 2^step_exponent, so the rates set are in steps.
+#[derive(Debug)]
+pub struct Scheduler {
+    pub speed: Speed,
+    pub gps: u32,
+    pub gens_per_frame: u32,
+    pub max_fps: u32,
+    pub step_exponent: u32,
+    next_generation: Instant,
+    next_frame: Instant,
+    // Whether this frame's generations are done, running a number per frame.
+    frame_done: bool,
+    generations: Rate,
+    frames: Rate,
+}
+
+impl Scheduler {
+    pub fn new(
+        speed: Speed,
+        gps: u32,
+        gens_per_frame: u32,
+        max_fps: u32,
+        step_exponent: u32,
+    ) -> Self {
+        let now = Instant::now();
+        Scheduler {
+            speed,
+            gps: gps.max(1),
+            gens_per_frame: gens_per_frame.max(1),
+            max_fps: max_fps.max(1),
+            step_exponent: step_exponent.min(MAX_STEP_EXPONENT),
+            next_generation: now,
+            next_frame: now,
+            frame_done: false,
+            generations: Rate::default(),
+            frames: Rate::default(),
+        }
+    }
+
+    fn generation_interval(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.gps as f64)
+    }
+
+    fn frame_interval(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.max_fps as f64)
+    }
+
+    // Keep the schedule at the present while stopped, so the time isn't caught up when started.
+    pub fn stopped(&mut self, now: Instant) {
+        self.next_generation = now;
+        self.frame_done = false;
+    }
+
+    // Generations in each step.
+    pub fn step(&self) -> usize {
+        1 << self.step_exponent
+    }
+
+    // The steps due now while running.
+    pub fn due(&self, now: Instant) -> Budget {
+        match self.speed {
+            Speed::Gps if now >= self.next_generation => Budget {
+                count: ((now - self.next_generation).as_secs_f64() * self.gps as f64) as u64 + 1,
+                deadline: Some(now + self.frame_interval()),
+            },
+            Speed::Gps => Budget {
+                count: 0,
+                deadline: None,
+            },
+            Speed::PerFrame => Budget {
+                count: if self.frame_done || now < self.next_frame {
+                    0
+                } else {
+                    self.gens_per_frame as u64
+                },
+                deadline: None,
+            },
+            // Computing until the next frame is due, and for at least one generation.
+            Speed::Max => Budget {
+                count: u64::MAX,
+                deadline: Some(self.next_frame),
+            },
+        }
+    }
+
+    pub fn steps_done(&mut self, n: u64, now: Instant) {
+        if n == 0 {
+            return;
+        }
+        self.generations.add(now, n << self.step_exponent);
+
+        match self.speed {
+            Speed::Gps => {
+                self.next_generation += self.generation_interval().mul_f64(n as f64);
+                if self.next_generation + MAX_LAG < now {
+                    self.next_generation = now;
+                }
+            }
+            Speed::PerFrame => self.frame_done = true,
+            Speed::Max => {}
+        }
+    }
+
+    // Whether a changed view can be drawn now without exceeding the maximum frame rate.
+    pub fn frame_due(&self, now: Instant) -> bool {
+        now >= self.next_frame
+    }
+
+    pub fn frame_drawn(&mut self, now: Instant) {
+        self.frames.add(now, 1);
+        self.frame_done = false;
+        self.next_frame = (self.next_frame + self.frame_interval()).max(now);
+    }
+
+    pub fn next_frame(&self) -> Instant {
+        self.next_frame
+    }
+
+    // When the next generations are due while running.
+    pub fn next_generation(&self) -> Instant {
+        match self.speed {
+            Speed::Gps => self.next_generation,
+            Speed::PerFrame => self.next_frame,
+            Speed::Max => Instant::now(),
+        }
+    }
+
+    // Generations and frames per second actually achieved recently.
+    pub fn rates(&self, now: Instant) -> (f64, f64) {
+        (
+            self.generations.per_second(now),
+            self.frames.per_second(now),
+        )
+    }
+
+    // Speed up or slow down by about 10%, in generations per second or per frame.
+    pub fn faster(&mut self) {
+        match self.speed {
+            Speed::Gps => self.gps += u32::max(self.gps / 10, 1),
+            Speed::PerFrame => self.gens_per_frame += u32::max(self.gens_per_frame / 10, 1),
+            Speed::Max => {}
+        }
+    }
+
+    pub fn slower(&mut self) {
+        match self.speed {
+            Speed::Gps => self.gps = (self.gps - u32::max(self.gps / 10, 1)).max(1),
+            Speed::PerFrame => {
+                self.gens_per_frame =
+                    (self.gens_per_frame - u32::max(self.gens_per_frame / 10, 1)).max(1)
+            }
+            Speed::Max => {}
+        }
+    }
+
+    // Double or halve the generations in each step.
+    pub fn bigger_step(&mut self) {
+        self.step_exponent = (self.step_exponent + 1).min(MAX_STEP_EXPONENT);
+    }
+
+    pub fn smaller_step(&mut self) {
+        self.step_exponent = self.step_exponent.saturating_sub(1);
+    }
+
+    pub fn label(&self) -> String {
+        match self.speed {
+            Speed::Gps => format!("{}/s", self.gps),
+            Speed::PerFrame => format!("{}/frame", self.gens_per_frame),
+            Speed::Max => "max".to_owned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn generations_per_second() {
+        let mut scheduler = Scheduler::new(Speed::Gps, 10, 1, 50, 0);
+        let start = Instant::now();
+        scheduler.stopped(start);
+
+        // One generation is due at once, then every 100ms.
+        assert_eq!(scheduler.due(start).count, 1);
+        scheduler.steps_done(1, start);
+        assert_eq!(scheduler.due(start + ms(50)).count, 0);
+        assert_eq!(scheduler.next_generation(), start + ms(100));
+        assert_eq!(scheduler.due(start + ms(250)).count, 2);
+
+        // Falling far behind, the schedule restarts from now rather than catching up.
+        scheduler.steps_done(1, start + ms(1000));
+        assert_eq!(scheduler.next_generation(), start + ms(1000));
+
+        scheduler.slower();
+        assert_eq!((scheduler.gps, scheduler.label().as_str()), (9, "9/s"));
+
+        // Steps double up to the limit.
+        scheduler.bigger_step();
+        assert_eq!(scheduler.step(), 2);
+        scheduler.step_exponent = MAX_STEP_EXPONENT;
+        scheduler.bigger_step();
+        assert_eq!(scheduler.step_exponent, MAX_STEP_EXPONENT);
+    }
+
+    #[test]
+    fn generations_per_frame() {
+        let mut scheduler = Scheduler::new(Speed::PerFrame, 10, 8, 50, 0);
+        let start = Instant::now();
+        scheduler.stopped(start);
+
+        // Once per frame, with frames at most every 20ms.
+        assert_eq!(
+            scheduler.due(start),
+            Budget {
+                count: 8,
+                deadline: None
+            }
+        );
+        scheduler.steps_done(8, start);
+        assert_eq!(scheduler.due(start).count, 0);
+        scheduler.frame_drawn(start);
+        assert!(!scheduler.frame_due(start + ms(10)));
+        assert_eq!(scheduler.due(start + ms(10)).count, 0);
+        assert_eq!(scheduler.due(start + ms(20)).count, 8);
+
+        // As fast as possible, generations fill the time until the next frame.
+        scheduler.speed = Speed::Max;
+        let next_frame = scheduler.next_frame();
+        assert!(next_frame > start + ms(10) && next_frame <= start + ms(20));
+        assert_eq!(scheduler.due(start + ms(10)).deadline, Some(next_frame));
+    }
+
+    #[test]
+    fn achieved_rates() {
+        let mut scheduler = Scheduler::new(Speed::Max, 10, 1, 60, 2);
+        let start = Instant::now();
+        for i in 0..=10 {
+            let now = start + ms(i * 50);
+            scheduler.steps_done(25, now);
+            scheduler.frame_drawn(now);
+        }
+
+        // Steps of 4 generations are counted as generations.
+        let (gps, fps) = scheduler.rates(start + ms(500));
+        assert!((gps - 2000.0).abs() < 1.0);
+        assert!((fps - 20.0).abs() < 0.1);
+
+        // Nothing for a while, nothing achieved.
+        assert_eq!(scheduler.rates(start + ms(5000)), (0.0, 0.0));
+    }
+}
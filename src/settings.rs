@@ -0,0 +1,1252 @@
+use anyhow::{anyhow, Context, Result};
+use directories::ProjectDirs;
+use log::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use boundlesse::grid::Backend;
+use boundlesse::objects::Separation;
+use boundlesse::render::Style;
+use boundlesse::rule::Rule;
+
+// Per-project settings, read from the current directory.
+pub const PROJECT_CONFIG: &str = "config.toml";
+
+// Steps are at most 2^MAX_STEP_EXPONENT generations.
+pub const MAX_STEP_EXPONENT: u32 = 20;
+
+// Colours as #rrggbb or #rrggbbaa, each replacing the theme's colour when given.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Colors {
+    pub background: Option<String>,
+    pub cell: Option<String>,
+    pub line: Option<String>,
+    pub text: Option<String>,
+    pub object: Option<String>,
+    pub age: Option<Vec<String>>,
+    pub born: Option<String>,
+    pub died: Option<String>,
+    pub trail: Option<String>,
+    pub dying: Option<Vec<String>>,
+}
+
+// The colours everything is drawn in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    pub background: String,
+    pub cell: String,
+    pub line: String,
+    pub text: String,
+    pub object: String,
+    // Cell colours by age, from just born to max-age generations and older.
+    pub age: Vec<String>,
+    pub born: String,
+    pub died: String,
+    pub trail: String,
+    // Dying cell colours under Generations rules, from the first dying state to the last.
+    pub dying: Vec<String>,
+}
+
+// Built-in themes, the first being the default.
+pub const BUILTIN_THEMES: &[&str] = &["light", "dark", "high-contrast", "golly-classic"];
+
+fn builtin_theme(name: &str) -> Option<Theme> {
+    // Background, cell, line, text, object, born, died and trail, then the age and dying colours.
+    let (colors, age, dying): ([&str; 8], &[&str], &[&str]) = match name {
+        "light" => (
+            [
+                "#ffffff", "#000000", "#808080", "#000000", "#e03030", "#30b030", "#e03030",
+                "#ff8000",
+            ],
+            &["#e03030", "#e0a000", "#30a030", "#3050d0", "#000000"],
+            &["#3050d0", "#c8d0f0"],
+        ),
+        "dark" => (
+            [
+                "#14181c", "#e0e0e0", "#343c44", "#c8ccd0", "#ff6060", "#60e060", "#ff6060",
+                "#ff9030",
+            ],
+            &["#ff6060", "#ffc040", "#60d060", "#60a0ff", "#e0e0e0"],
+            &["#60a0ff", "#283848"],
+        ),
+        "high-contrast" => (
+            [
+                "#000000", "#ffffff", "#707070", "#ffff00", "#00ffff", "#00ff00", "#ff0000",
+                "#ff00ff",
+            ],
+            &["#ff0000", "#ffff00", "#00ff00", "#00ffff", "#ffffff"],
+            &["#00ffff", "#0000ff"],
+        ),
+        "golly-classic" => (
+            [
+                "#303030", "#ffffff", "#505050", "#ffffff", "#ffff00", "#00ff00", "#ff0000",
+                "#0080ff",
+            ],
+            &["#ffff00", "#ff8000", "#ff0000", "#800080", "#ffffff"],
+            &["#ff0000", "#800000"],
+        ),
+        _ => return None,
+    };
+    let [background, cell, line, text, object, born, died, trail] = colors.map(str::to_owned);
+
+    Some(Theme {
+        background,
+        cell,
+        line,
+        text,
+        object,
+        age: age.iter().map(|&c| c.to_owned()).collect(),
+        born,
+        died,
+        trail,
+        dying: dying.iter().map(|&c| c.to_owned()).collect(),
+    })
+}
+
+impl Theme {
+    fn overlay(&mut self, colors: &Colors) {
+        let colors = colors.clone();
+        for (theme, color) in [
+            (&mut self.background, colors.background),
+            (&mut self.cell, colors.cell),
+            (&mut self.line, colors.line),
+            (&mut self.text, colors.text),
+            (&mut self.object, colors.object),
+            (&mut self.born, colors.born),
+            (&mut self.died, colors.died),
+            (&mut self.trail, colors.trail),
+        ] {
+            if let Some(color) = color {
+                *theme = color;
+            }
+        }
+        if let Some(age) = colors.age {
+            self.age = age;
+        }
+        if let Some(dying) = colors.dying {
+            self.dying = dying;
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.age.is_empty() || self.dying.is_empty() {
+            return Err(anyhow!("Age and dying colours need at least one colour"));
+        }
+
+        for c in [
+            &self.background,
+            &self.cell,
+            &self.line,
+            &self.text,
+            &self.object,
+            &self.born,
+            &self.died,
+            &self.trail,
+        ]
+        .into_iter()
+        .chain(&self.age)
+        .chain(&self.dying)
+        {
+            parse_color(c)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn default_base() -> String {
+    BUILTIN_THEMES[0].to_owned()
+}
+
+// A theme defined in the settings, changing some of the colours of a built-in theme.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct CustomTheme {
+    #[serde(default = "default_base")]
+    pub base: String,
+    #[serde(flatten)]
+    pub colors: Colors,
+}
+
+// How a pixel covering many cells is drawn.
+#[derive(Serialize, Deserialize, Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SubPixel {
+    // Shaded by the fraction of the cells alive.
+    #[default]
+    Density,
+    // Solid if any of the cells are alive.
+    AnyAlive,
+}
+
+// How live cells are coloured, beyond plain.
+#[derive(Serialize, Deserialize, Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum CellColors {
+    #[default]
+    Plain,
+    // By the generations since each cell was born.
+    Age,
+    // Cells born in the latest generation and those which just died stand out.
+    Changes,
+    // Recently dead cells leave a fading trail.
+    Heat,
+}
+
+impl CellColors {
+    pub fn next(self) -> Self {
+        match self {
+            CellColors::Plain => CellColors::Age,
+            CellColors::Age => CellColors::Changes,
+            CellColors::Changes => CellColors::Heat,
+            CellColors::Heat => CellColors::Plain,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CellColors::Plain => "plain",
+            CellColors::Age => "age",
+            CellColors::Changes => "changes",
+            CellColors::Heat => "heat",
+        }
+    }
+}
+
+// How fast the universe runs in the app.
+#[derive(Serialize, Deserialize, Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Speed {
+    // At the target generations per second.
+    #[default]
+    Gps,
+    // A number of generations each frame.
+    PerFrame,
+    // As many generations as fit between frames.
+    Max,
+}
+
+impl Speed {
+    pub fn next(self) -> Self {
+        match self {
+            Speed::Gps => Speed::PerFrame,
+            Speed::PerFrame => Speed::Max,
+            Speed::Max => Speed::Gps,
+        }
+    }
+}
+
+// How recordings started in the app are written.
+#[derive(Serialize, Deserialize, Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RecordFormat {
+    #[default]
+    Gif,
+    // Numbered PNG files.
+    Png,
+}
+
+// Note: tables (colors, themes, keys) must follow the plain values for TOML serialisation.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Settings {
+    pub speed: Speed,
+    pub gps: u32,
+    pub gens_per_frame: u32,
+    // Frames are drawn at most this often, however fast the universe runs.
+    pub max_fps: u32,
+    // Each step advances 2^step-exponent generations, only the last being drawn.
+    pub step_exponent: u32,
+    pub rule: Rule,
+    pub backend: Backend,
+
+    // Worker threads for soup search, 0 for one per core.
+    pub threads: usize,
+
+    pub show_grid: bool,
+    pub show_rulers: bool,
+    pub show_header: bool,
+    pub header_font_size: f32,
+    pub show_objects: bool,
+    pub show_minimap: bool,
+    pub sub_pixel: SubPixel,
+    pub cell_colors: CellColors,
+    // Generations over which the age colours run, and the heat trail fades.
+    pub max_age: u32,
+    pub trail_length: u32,
+    pub theme: String,
+    // Exported images: pixels per cell, and whether lines are drawn between cells.
+    pub image_cell_size: f64,
+    pub image_grid: bool,
+    // Recordings: frames, generations between frames, milliseconds per frame and format.
+    pub record_frames: usize,
+    pub record_step: usize,
+    pub record_delay: u64,
+    pub record_format: RecordFormat,
+
+    // How the pattern is separated into objects for labelling.
+    pub separation: Separation,
+    // Colours replacing those of every theme.
+    pub colors: Colors,
+    pub themes: BTreeMap<String, CustomTheme>,
+
+    // Action name -> key names, replacing the default bindings for that action.
+    pub keys: BTreeMap<String, Vec<String>>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            speed: Speed::default(),
+            gps: 10,
+            gens_per_frame: 1,
+            max_fps: 60,
+            step_exponent: 0,
+            rule: Rule::default(),
+            backend: Backend::default(),
+            threads: 0,
+            show_grid: true,
+            show_rulers: false,
+            show_header: true,
+            header_font_size: 40.0,
+            show_objects: false,
+            show_minimap: true,
+            sub_pixel: SubPixel::default(),
+            cell_colors: CellColors::default(),
+            max_age: 100,
+            trail_length: 50,
+            theme: default_base(),
+            image_cell_size: 8.0,
+            image_grid: false,
+            record_frames: 100,
+            record_step: 1,
+            record_delay: 100,
+            record_format: RecordFormat::default(),
+            separation: Separation::default(),
+            colors: Colors::default(),
+            themes: BTreeMap::new(),
+            keys: BTreeMap::new(),
+        }
+    }
+}
+
+pub fn user_config_path() -> Option<PathBuf> {
+    ProjectDirs::from("", "", "boundlesse").map(|d| d.config_dir().join("config.toml"))
+}
+
+// Overlay the values in `over` onto `base`, merging tables key by key.
+fn merge(base: &mut toml::Value, over: toml::Value) {
+    match (base, over) {
+        (toml::Value::Table(base), toml::Value::Table(over)) => {
+            for (k, v) in over {
+                match base.get_mut(&k) {
+                    Some(b) => merge(b, v),
+                    None => {
+                        base.insert(k, v);
+                    }
+                }
+            }
+        }
+        (base, over) => *base = over,
+    }
+}
+
+fn read_layer(path: &Path) -> Result<toml::Value> {
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))?;
+    toml::from_str(&contents).with_context(|| format!("Parsing {}", path.display()))
+}
+
+// A settings file the app can save to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SaveTarget {
+    User,
+    // The per-project file, or the file given explicitly in its place.
+    Project,
+}
+
+// The settings files, each overlaying those before it.
+#[derive(Debug, Clone)]
+pub struct Layers {
+    user: Option<PathBuf>,
+    project: PathBuf,
+    explicit: Option<PathBuf>,
+}
+
+impl Layers {
+    pub fn new(explicit: Option<&Path>) -> Layers {
+        Layers {
+            user: user_config_path(),
+            project: PathBuf::from(PROJECT_CONFIG),
+            explicit: explicit.map(Path::to_path_buf),
+        }
+    }
+
+    pub fn path(&self, target: SaveTarget) -> Option<&Path> {
+        match target {
+            SaveTarget::User => self.user.as_deref(),
+            SaveTarget::Project => Some(self.explicit.as_ref().unwrap_or(&self.project)),
+        }
+    }
+
+    // The defaults overlaid by the files beneath the target, or by all the files.
+    fn merged(&self, beneath: Option<SaveTarget>) -> Result<toml::Value> {
+        let mut layers: Vec<&Path> = vec![];
+        if beneath != Some(SaveTarget::User) {
+            layers.extend(self.user.as_deref());
+            if beneath.is_none() || self.explicit.is_some() {
+                layers.push(&self.project);
+            }
+        }
+
+        let mut merged = toml::Value::try_from(Settings::default())?;
+        for path in layers {
+            if path.exists() {
+                info!("Loading settings: {}", path.display());
+                merge(&mut merged, read_layer(path)?);
+            }
+        }
+
+        if let (None, Some(path)) = (beneath, &self.explicit) {
+            info!("Loading settings: {}", path.display());
+            merge(&mut merged, read_layer(path)?);
+        }
+
+        Ok(merged)
+    }
+
+    // Load the per-user settings overlaid by the per-project (or explicitly given) settings.
+    pub fn load(&self) -> Result<Settings> {
+        let settings: Settings = self.merged(None)?.try_into()?;
+        settings.validate()?;
+
+        Ok(settings)
+    }
+
+    // Save the settings changed since they were loaded as `start` to the target file. Only
+    // values differing from the files beneath are written, settings no longer set are taken out,
+    // and the rest of the file, comments included, is kept. A file which can't be edited so is
+    // left alone, with an error.
+    pub fn save(
+        &self,
+        target: SaveTarget,
+        start: &Settings,
+        current: &Settings,
+    ) -> Result<PathBuf> {
+        let path = self
+            .path(target)
+            .ok_or(anyhow!("No per-user settings directory"))?;
+
+        let below = self.merged(Some(target))?;
+        let text = if path.exists() {
+            fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))?
+        } else {
+            String::new()
+        };
+        let file: toml::Value =
+            toml::from_str(&text).with_context(|| format!("Parsing {}", path.display()))?;
+
+        // Values given on the command line are only saved once changed in the app.
+        let mut expected = file.clone();
+        let mut text = text;
+        let changed = changes(
+            &toml::Value::try_from(start)?,
+            &toml::Value::try_from(current)?,
+            &[],
+        );
+        for (table, key, value) in changed {
+            let in_file = lookup(&file, &table, &key);
+            let beneath = lookup(&below, &table, &key);
+            let value = match value {
+                Some(v) if in_file == Some(&v) || (in_file.is_none() && beneath == Some(&v)) => {
+                    continue
+                }
+                Some(v) => Some(v),
+                None if beneath.is_some() => {
+                    return Err(anyhow!(
+                        "Can't remove {} from {}, as it is set in the settings beneath",
+                        table
+                            .iter()
+                            .chain([&key])
+                            .cloned()
+                            .collect::<Vec<_>>()
+                            .join("."),
+                        path.display()
+                    ))
+                }
+                None if in_file.is_none() => continue,
+                None => None,
+            };
+            text = edit(&text, &table, &key, value.as_ref())
+                .map_err(|e| anyhow!("Can't edit {}: {}, edit it by hand", path.display(), e))?;
+            apply(&mut expected, &table, &key, value.as_ref());
+        }
+
+        // Check nothing else changed, in case the file is laid out in a way not catered for.
+        let edited = toml::from_str(&text).ok().map(prune);
+        if edited != Some(prune(expected)) {
+            return Err(anyhow!(
+                "Can't edit {}: its layout isn't supported, edit it by hand",
+                path.display()
+            ));
+        }
+
+        if let Some(dir) = path.parent() {
+            if !dir.as_os_str().is_empty() {
+                fs::create_dir_all(dir)?;
+            }
+        }
+        fs::write(path, text).with_context(|| format!("Writing {}", path.display()))?;
+
+        Ok(path.to_path_buf())
+    }
+}
+
+// The values in `to` which differ from those in `from`, each with the tables it is in, its key
+// and its new value, or None where `to` no longer has it.
+fn changes(
+    from: &toml::Value,
+    to: &toml::Value,
+    table: &[String],
+) -> Vec<(Vec<String>, String, Option<toml::Value>)> {
+    let mut changed = vec![];
+    let (Some(from), Some(to)) = (from.as_table(), to.as_table()) else {
+        return changed;
+    };
+    for (k, v) in to {
+        match (from.get(k), v) {
+            (Some(f @ toml::Value::Table(_)), toml::Value::Table(_)) => {
+                let mut nested = table.to_vec();
+                nested.push(k.clone());
+                changed.extend(changes(f, v, &nested));
+            }
+            (None, toml::Value::Table(_)) => {
+                let mut nested = table.to_vec();
+                nested.push(k.clone());
+                changed.extend(
+                    leaves(v, nested)
+                        .into_iter()
+                        .map(|(t, k, v)| (t, k, Some(v))),
+                );
+            }
+            (f, v) if f != Some(v) => changed.push((table.to_vec(), k.clone(), Some(v.clone()))),
+            _ => {}
+        }
+    }
+    for k in from.keys().filter(|k| !to.contains_key(*k)) {
+        changed.push((table.to_vec(), k.clone(), None));
+    }
+    changed
+}
+
+// Each value other than a table, with the tables it is in and its key.
+fn leaves(value: &toml::Value, table: Vec<String>) -> Vec<(Vec<String>, String, toml::Value)> {
+    let mut found = vec![];
+    if let toml::Value::Table(t) = value {
+        for (k, v) in t {
+            match v {
+                toml::Value::Table(_) => {
+                    let mut nested = table.clone();
+                    nested.push(k.clone());
+                    found.extend(leaves(v, nested));
+                }
+                v => found.push((table.clone(), k.clone(), v.clone())),
+            }
+        }
+    }
+    found
+}
+
+fn lookup<'a>(value: &'a toml::Value, table: &[String], key: &str) -> Option<&'a toml::Value> {
+    table
+        .iter()
+        .try_fold(value, |v, t| v.get(t))
+        .and_then(|v| v.get(key))
+}
+
+// Set or, given None, remove a value among the nested tables of `value`.
+fn apply(value: &mut toml::Value, table: &[String], key: &str, new: Option<&toml::Value>) {
+    let mut v = value;
+    for t in table {
+        let Some(tables) = v.as_table_mut() else {
+            return;
+        };
+        v = tables
+            .entry(t.clone())
+            .or_insert_with(|| toml::Value::Table(toml::value::Table::new()));
+    }
+    if let Some(tables) = v.as_table_mut() {
+        match new {
+            Some(new) => tables.insert(key.to_owned(), new.clone()),
+            None => tables.remove(key),
+        };
+    }
+}
+
+// The value without any empty tables, which are the same as none.
+fn prune(value: toml::Value) -> toml::Value {
+    match value {
+        toml::Value::Table(t) => toml::Value::Table(
+            t.into_iter()
+                .map(|(k, v)| (k, prune(v)))
+                .filter(|(_, v)| v.as_table().is_none_or(|t| !t.is_empty()))
+                .collect(),
+        ),
+        v => v,
+    }
+}
+
+fn format_key(key: &str) -> String {
+    if !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        key.to_owned()
+    } else {
+        toml::Value::String(key.to_owned()).to_string()
+    }
+}
+
+// The byte offset of the quote closing a basic string, `s` starting just after the opening one.
+fn closing_quote(s: &str, quote: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if s[i..].starts_with(quote) {
+            return Some(i);
+        }
+    }
+    None
+}
+
+// The dotted parts of the key at the start of `s`, bare or quoted, and the rest of `s`.
+fn parse_key(s: &str) -> Option<(Vec<String>, &str)> {
+    let mut parts = vec![];
+    let mut rest = s.trim_start();
+    loop {
+        let (part, after) = if let Some(r) = rest.strip_prefix('"') {
+            let end = closing_quote(r, "\"")?;
+            let quoted: toml::Value = toml::from_str(&format!("k = {}", &rest[..end + 2])).ok()?;
+            (quoted.get("k")?.as_str()?.to_owned(), &r[end + 1..])
+        } else if let Some(r) = rest.strip_prefix('\'') {
+            let end = r.find('\'')?;
+            (r[..end].to_owned(), &r[end + 1..])
+        } else {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+                .unwrap_or(rest.len());
+            if end == 0 {
+                return None;
+            }
+            (rest[..end].to_owned(), &rest[end..])
+        };
+        parts.push(part);
+        rest = after.trim_start();
+        match rest.strip_prefix('.') {
+            Some(r) => rest = r,
+            None => return Some((parts, rest)),
+        }
+    }
+}
+
+// Follows a value over the lines it spans: the arrays and inline tables left open, and any
+// multi-line string, ignoring brackets in strings and comments.
+#[derive(Default)]
+struct ValueScan {
+    depth: i64,
+    string: Option<&'static str>,
+}
+
+impl ValueScan {
+    // Scan the next line of the value, returning whether the value ends on it.
+    fn line(&mut self, line: &str) -> bool {
+        let mut rest = line;
+        while let Some(c) = rest.chars().next() {
+            if let Some(quote) = self.string {
+                let end = if quote == "'''" {
+                    rest.find(quote)
+                } else {
+                    closing_quote(rest, quote)
+                };
+                match end {
+                    Some(i) => {
+                        rest = &rest[i + quote.len()..];
+                        self.string = None;
+                        continue;
+                    }
+                    None => return false,
+                }
+            }
+            if rest.starts_with("\"\"\"") || rest.starts_with("'''") {
+                self.string = Some(if c == '"' { "\"\"\"" } else { "'''" });
+                rest = &rest[3..];
+                continue;
+            }
+            match c {
+                '#' => break,
+                '"' | '\'' => {
+                    let r = &rest[1..];
+                    let end = if c == '"' {
+                        closing_quote(r, "\"")
+                    } else {
+                        r.find('\'')
+                    };
+                    rest = end.map_or("", |i| &r[i + 1..]);
+                    continue;
+                }
+                '[' | '{' => self.depth += 1,
+                ']' | '}' => self.depth -= 1,
+                _ => {}
+            }
+            rest = &rest[c.len_utf8()..];
+        }
+        self.string.is_none() && self.depth <= 0
+    }
+}
+
+// What a line of TOML holds, or lines for a value spanning several.
+#[derive(Debug)]
+enum Item {
+    // A `[table]` header, or a `[[table]]` one for an array of tables.
+    Header(Vec<String>, bool),
+    // A `key = value`, with the dotted parts of the key and whether the value is an inline
+    // table.
+    Value(Vec<String>, bool),
+    // A comment or blank line.
+    Other,
+}
+
+// The items of TOML text, each with its first and last line.
+fn items(lines: &[String]) -> Vec<(usize, usize, Item)> {
+    let mut items = vec![];
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i].trim();
+        let mut end = i;
+        let item = if let Some(h) = line.strip_prefix("[[") {
+            Item::Header(parse_key(h).map(|(k, _)| k).unwrap_or_default(), true)
+        } else if let Some(h) = line.strip_prefix('[') {
+            Item::Header(parse_key(h).map(|(k, _)| k).unwrap_or_default(), false)
+        } else if let Some((key, rest)) = parse_key(line).filter(|(_, r)| r.starts_with('=')) {
+            let value = rest[1..].trim_start();
+            let mut scan = ValueScan::default();
+            if !scan.line(value) {
+                while end + 1 < lines.len() {
+                    end += 1;
+                    if scan.line(&lines[end]) {
+                        break;
+                    }
+                }
+            }
+            Item::Value(key, value.starts_with('{'))
+        } else {
+            Item::Other
+        };
+        items.push((i, end, item));
+        i = end + 1;
+    }
+    items
+}
+
+// Set or, given None, remove a value in TOML text, keeping the rest of the text. A new
+// top-level value goes after a commented out example of it, if there is one. Values in tables
+// written with dotted keys, inline tables or arrays of tables aren't edited, and are an error.
+fn edit(text: &str, table: &[String], key: &str, value: Option<&toml::Value>) -> Result<String> {
+    let mut lines: Vec<String> = text.lines().map(str::to_owned).collect();
+    let mut path = table.to_vec();
+    path.push(key.to_owned());
+    let name = path.join(".");
+
+    let mut section: Vec<String> = vec![];
+    let mut in_comment_section = false;
+    let mut first_header = None;
+    let mut last_in_table = None;
+    let mut example = None;
+    let mut found = None;
+    // Sections of a table being removed.
+    let mut removed: Vec<(usize, usize)> = vec![];
+    for (start, end, item) in items(&lines) {
+        match item {
+            Item::Header(h, array) => {
+                if array && path.starts_with(&h) {
+                    return Err(anyhow!("{name} is in an array of tables"));
+                }
+                in_comment_section = false;
+                first_header.get_or_insert(start);
+                if !array && h == table {
+                    last_in_table = Some(end);
+                }
+                if value.is_none() && h.starts_with(&path) {
+                    removed.push((start, end));
+                }
+                section = h;
+            }
+            Item::Value(k, inline) => {
+                let mut full = section.clone();
+                full.extend(k.iter().cloned());
+                if k.len() > 1 && path.starts_with(&full[..section.len() + 1]) {
+                    return Err(anyhow!("{name} is set with a dotted key"));
+                }
+                if inline && path.starts_with(&full) {
+                    return Err(anyhow!("{name} is in an inline table"));
+                }
+                if full == path {
+                    found = Some((start, end));
+                } else if section == table && !table.is_empty() {
+                    last_in_table = Some(end);
+                }
+                if let Some(last) = removed.last_mut().filter(|_| section.starts_with(&path)) {
+                    last.1 = end;
+                }
+            }
+            Item::Other => {
+                let Some(commented) = lines[start].trim_start().strip_prefix('#') else {
+                    continue;
+                };
+                if commented.trim_start().starts_with('[') {
+                    in_comment_section = true;
+                } else if table.is_empty()
+                    && !in_comment_section
+                    && first_header.is_none()
+                    && parse_key(commented)
+                        .filter(|(_, r)| r.starts_with('='))
+                        .is_some_and(|(k, _)| k == [key])
+                {
+                    example = Some(start);
+                }
+            }
+        }
+    }
+
+    let entry = value.map(|v| format!("{} = {}", format_key(key), v));
+    if let Some((start, end)) = found {
+        lines.splice(start..=end, entry);
+        return Ok(join_lines(lines));
+    }
+    let Some(entry) = entry else {
+        for &(start, end) in removed.iter().rev() {
+            lines.drain(start..=end);
+            // Keep a single blank line where the section was.
+            let blank = |i: usize| lines.get(i).is_some_and(|l| l.trim().is_empty());
+            if blank(start) && (start == 0 || blank(start - 1)) {
+                lines.remove(start);
+            }
+        }
+        return Ok(join_lines(lines));
+    };
+
+    match (example, last_in_table, first_header) {
+        (Some(i), _, _) | (None, Some(i), _) => lines.insert(i + 1, entry),
+        (None, None, Some(i)) if table.is_empty() => {
+            lines.insert(i, String::new());
+            lines.insert(i, entry);
+        }
+        _ if table.is_empty() => lines.push(entry),
+        _ => {
+            if lines.last().is_some_and(|l| !l.trim().is_empty()) {
+                lines.push(String::new());
+            }
+            let names: Vec<String> = table.iter().map(|t| format_key(t)).collect();
+            lines.push(format!("[{}]", names.join(".")));
+            lines.push(entry);
+        }
+    }
+
+    Ok(join_lines(lines))
+}
+
+fn join_lines(lines: Vec<String>) -> String {
+    let mut text = lines.join("\n");
+    text.push('\n');
+    text
+}
+
+impl Settings {
+    fn validate(&self) -> Result<()> {
+        if self.gps == 0 || self.gens_per_frame == 0 || self.max_fps == 0 {
+            return Err(anyhow!(
+                "Settings gps, gens-per-frame and max-fps must be at least 1"
+            ));
+        }
+        if self.step_exponent > MAX_STEP_EXPONENT {
+            return Err(anyhow!(
+                "Setting step-exponent must be at most {MAX_STEP_EXPONENT}"
+            ));
+        }
+        if self.header_font_size <= 0.0 {
+            return Err(anyhow!("Setting header-font-size must be positive"));
+        }
+        if self.separation.merge_distance < 1 {
+            return Err(anyhow!(
+                "Setting separation.merge-distance must be at least 1"
+            ));
+        }
+
+        if self.image_cell_size.is_nan() || self.image_cell_size <= 0.0 {
+            return Err(anyhow!("Setting image-cell-size must be positive"));
+        }
+        if self.record_frames == 0 || self.record_step == 0 {
+            return Err(anyhow!(
+                "Settings record-frames and record-step must be at least 1"
+            ));
+        }
+        if self.max_age == 0 || self.trail_length == 0 {
+            return Err(anyhow!(
+                "Settings max-age and trail-length must be at least 1"
+            ));
+        }
+        self.theme(&self.theme)?;
+        for name in self.theme_names() {
+            self.theme(&name)
+                .and_then(|t| t.validate())
+                .with_context(|| format!("Theme {name}"))?;
+        }
+
+        Ok(())
+    }
+
+    // How exported images are drawn, in the colours of the named theme.
+    pub fn image_style(&self, theme: &str) -> Result<Style> {
+        let theme = self.theme(theme)?;
+        Ok(Style {
+            cell_size: self.image_cell_size,
+            background: parse_color(&theme.background)?,
+            cell: parse_color(&theme.cell)?,
+            dying: theme
+                .dying
+                .iter()
+                .map(|c| parse_color(c))
+                .collect::<Result<_>>()?,
+            grid: if self.image_grid {
+                Some(parse_color(&theme.line)?)
+            } else {
+                None
+            },
+        })
+    }
+
+    // The built-in themes followed by those defined in the settings.
+    pub fn theme_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = BUILTIN_THEMES.iter().map(|&t| t.to_owned()).collect();
+        for name in self.themes.keys() {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+        names
+    }
+
+    // The colours of the named theme, with any overridden in [colors].
+    pub fn theme(&self, name: &str) -> Result<Theme> {
+        let mut theme = match self.themes.get(name) {
+            Some(custom) => {
+                let mut theme = builtin_theme(&custom.base).ok_or_else(|| {
+                    anyhow!("Theme {name} is based on unknown theme {}", custom.base)
+                })?;
+                theme.overlay(&custom.colors);
+                theme
+            }
+            None => builtin_theme(name).ok_or_else(|| anyhow!("Unknown theme: {name}"))?,
+        };
+        theme.overlay(&self.colors);
+
+        Ok(theme)
+    }
+}
+
+// Parse a #rrggbb or #rrggbbaa colour.
+pub fn parse_color(s: &str) -> Result<[u8; 4]> {
+    let hex = s
+        .strip_prefix('#')
+        .filter(|h| (h.len() == 6 || h.len() == 8) && h.is_ascii())
+        .ok_or(anyhow!("Invalid colour: {s}"))?;
+
+    let mut rgba = [255u8; 4];
+    for (i, c) in rgba.iter_mut().enumerate().take(hex.len() / 2) {
+        *c = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)
+            .map_err(|_| anyhow!("Invalid colour: {s}"))?;
+    }
+
+    Ok(rgba)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_colors() -> Result<()> {
+        assert_eq!(parse_color("#ff8000")?, [255, 128, 0, 255]);
+        assert_eq!(parse_color("#00000080")?, [0, 0, 0, 128]);
+
+        assert!(parse_color("ff8000").is_err());
+        assert!(parse_color("#ff80").is_err());
+        assert!(parse_color("#gg8000").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn merge_layers() -> Result<()> {
+        let mut merged: toml::Value = toml::from_str(
+            r##"
+            gps = 20
+            show-grid = false
+            [colors]
+            cell = "#ff0000"
+            "##,
+        )?;
+        let project: toml::Value = toml::from_str(
+            r##"
+            gps = 30
+            rule = "B36/S23"
+            [colors]
+            background = "#000000"
+            "##,
+        )?;
+        merge(&mut merged, project);
+
+        let settings: Settings = merged.try_into()?;
+
+        assert_eq!(settings.gps, 30);
+        assert!(!settings.show_grid);
+        assert!(settings.show_header);
+        assert_eq!(settings.rule.to_string(), "B36/S23");
+        assert_eq!(settings.colors.cell.as_deref(), Some("#ff0000"));
+        assert_eq!(settings.colors.background.as_deref(), Some("#000000"));
+        assert_eq!(settings.colors.line, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn round_trip() -> Result<()> {
+        let mut settings = Settings {
+            backend: Backend::Naive,
+            speed: Speed::PerFrame,
+            cell_colors: CellColors::Heat,
+            ..Default::default()
+        };
+        settings
+            .keys
+            .insert("zoom-in".to_owned(), vec!["Z".to_owned()]);
+        settings.themes.insert(
+            "mine".to_owned(),
+            CustomTheme {
+                base: "dark".to_owned(),
+                colors: Colors {
+                    cell: Some("#00ff00".to_owned()),
+                    ..Default::default()
+                },
+            },
+        );
+
+        let text = toml::to_string_pretty(&settings)?;
+        let loaded: Settings = toml::from_str(&text)?;
+
+        assert_eq!(settings, loaded);
+
+        Ok(())
+    }
+
+    #[test]
+    fn save_load_layers() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("boundlesse-settings-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let user = dir.join("user.toml");
+        let project = dir.join("config.toml");
+        fs::write(&user, "gps = 20\nshow-grid = false\n")?;
+        let template = "# Project settings.\n\n# gps = 10\n# show-header = true\n\n# [colors]\n# cell = \"#000000\"\n";
+        fs::write(&project, template)?;
+
+        let layers = Layers {
+            user: Some(user.clone()),
+            project: project.clone(),
+            explicit: None,
+        };
+        let loaded = layers.load()?;
+        assert_eq!(loaded.gps, 20);
+
+        // The rule is given on the command line, the rest changed in the app.
+        let start = Settings {
+            rule: "B36/S23".parse()?,
+            ..loaded
+        };
+        let mut current = start.clone();
+        current.show_header = false;
+        current.theme = "dark".to_owned();
+        current.colors.cell = Some("#ff0000".to_owned());
+        layers.save(SaveTarget::Project, &start, &current)?;
+
+        let text = fs::read_to_string(&project)?;
+        assert!(text.starts_with(
+            "# Project settings.\n\n# gps = 10\n# show-header = true\nshow-header = false\n"
+        ));
+        assert!(text.contains("theme = \"dark\""));
+        assert!(text.ends_with("[colors]\ncell = \"#ff0000\"\n"));
+        // Neither the flags nor the user's settings are copied into the project.
+        assert!(!text.contains("rule ="));
+        assert!(!text.contains("\ngps ="));
+        assert!(!text.contains("show-grid ="));
+
+        let reloaded = layers.load()?;
+        assert_eq!(reloaded.gps, 20);
+        assert!(!reloaded.show_grid);
+        assert!(!reloaded.show_header);
+        assert_eq!(reloaded.theme, "dark");
+        assert_eq!(reloaded.rule, Rule::default());
+        assert_eq!(reloaded.colors.cell.as_deref(), Some("#ff0000"));
+
+        // Saving again replaces values rather than repeating them.
+        let mut current = reloaded.clone();
+        current.gps = 30;
+        current.show_header = true;
+        layers.save(SaveTarget::User, &reloaded, &current)?;
+        layers.save(SaveTarget::Project, &reloaded, &current)?;
+
+        assert_eq!(fs::read_to_string(&user)?, "gps = 30\nshow-grid = false\n");
+        let text = fs::read_to_string(&project)?;
+        assert!(text.contains("\nshow-header = true\n"));
+        assert!(!text.contains("show-header = false"));
+        assert!(!text.contains("\ngps ="));
+
+        let reloaded = layers.load()?;
+        assert_eq!(reloaded.gps, 30);
+        assert!(reloaded.show_header);
+
+        fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn save_edits() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("boundlesse-edits-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let user = dir.join("user.toml");
+        let project = dir.join("config.toml");
+        fs::write(&user, "[colors]\nline = \"#808080\"\n")?;
+        let layers = Layers {
+            user: Some(user.clone()),
+            project: project.clone(),
+            explicit: None,
+        };
+
+        // Brackets in strings and comments don't end an array.
+        let text = r##"[colors]
+cell = "#ff0000"
+age = [
+    "#000000", # ] not the end
+    "#ffffff",
+]
+
+[themes.mine]
+base = "dark"
+
+[keys]
+toggle-run = ["P"]
+step = [
+    "]",
+    "N",
+]
+"##;
+        fs::write(&project, text)?;
+        let start = layers.load()?;
+        let mut current = start.clone();
+        current.colors.cell = None;
+        current.colors.background = Some("#ffffff".to_owned());
+        current.themes.remove("mine");
+        current.keys.remove("toggle-run");
+        layers.save(SaveTarget::Project, &start, &current)?;
+
+        let text = fs::read_to_string(&project)?;
+        assert!(text.contains("\"#ffffff\",\n]\n"));
+        assert!(text.contains("\"N\",\n]\n"));
+        assert!(!text.contains("cell ="));
+        assert!(!text.contains("mine"));
+        assert!(!text.contains("toggle-run"));
+        assert_eq!(layers.load()?, current);
+
+        // Values from the file beneath can't be removed.
+        let mut removed = current.clone();
+        removed.colors.line = None;
+        assert!(layers
+            .save(SaveTarget::Project, &current, &removed)
+            .is_err());
+
+        // Dotted keys and inline tables are reported rather than edited.
+        for (text, reason) in [
+            ("colors.cell = \"#000000\"\n", "dotted key"),
+            ("colors = { cell = \"#000000\" }\n", "inline table"),
+        ] {
+            fs::write(&project, text)?;
+            let start = layers.load()?;
+            let mut current = start.clone();
+            current.colors.cell = Some("#ff0000".to_owned());
+            let err = layers
+                .save(SaveTarget::Project, &start, &current)
+                .expect_err("Edited an unsupported layout");
+            assert!(err.to_string().contains(reason));
+            assert_eq!(fs::read_to_string(&project)?, text);
+        }
+
+        fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn themes() -> Result<()> {
+        let settings: Settings = toml::from_str(
+            r##"
+            theme = "mine"
+            [colors]
+            object = "#0000ff"
+            [themes.mine]
+            base = "dark"
+            cell = "#00ff00"
+            age = ["#ffffff"]
+            dying = ["#808080"]
+            [themes.plain]
+            line = "#c0c0c0"
+            "##,
+        )?;
+        settings.validate()?;
+
+        let mine = settings.theme("mine")?;
+        let dark = settings.theme("dark")?;
+        assert_eq!(mine.cell, "#00ff00");
+        assert_eq!(mine.background, dark.background);
+        assert_eq!(mine.age, vec!["#ffffff"]);
+        assert_eq!(mine.dying, vec!["#808080"]);
+        assert_eq!(dark.dying.len(), 2);
+        // The [colors] apply to every theme.
+        assert_eq!(mine.object, "#0000ff");
+        assert_eq!(dark.object, "#0000ff");
+
+        // Based on the first built-in theme by default.
+        let plain = settings.theme("plain")?;
+        assert_eq!(plain.background, settings.theme("light")?.background);
+        assert_eq!(plain.line, "#c0c0c0");
+
+        assert_eq!(settings.theme_names().len(), BUILTIN_THEMES.len() + 2);
+        assert!(settings.theme("missing").is_err());
+
+        let bad: Settings = toml::from_str(
+            r##"
+            [themes.bad]
+            base = "missing"
+            "##,
+        )?;
+        assert!(bad.validate().is_err());
+
+        Ok(())
+    }
+}
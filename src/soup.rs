@@ -0,0 +1,578 @@
+//! Searching random soups and taking a census of the objects left.
+
+use anyhow::{anyhow, Result};
+use log::*;
+use rand::distributions::Alphanumeric;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::apgcode::{apgcode, canonical};
+use crate::grid::{Backend, GridCoord};
+use crate::objects::{separate_independent, Separation};
+use crate::rule::Rule;
+
+pub const DEFAULT_SOUP_SIZE: (i64, i64) = (16, 16);
+
+// Soups still changing after this many generations are abandoned.
+const MAX_GENERATIONS: usize = 20_000;
+
+// A soup has stabilised when its population has repeated with a period of at most
+// MAX_ASH_PERIOD for STABLE_WINDOW generations.
+const MAX_ASH_PERIOD: usize = 30;
+const STABLE_WINDOW: usize = 120;
+
+// Longest period looked for when naming an object.
+const MAX_OBJECT_PERIOD: usize = 100;
+
+// Sample seeds kept for each object.
+const MAX_SAMPLES: usize = 3;
+
+// The usual B3/S23 ash. Anything else found is reported as rare.
+const COMMON: &[&str] = &[
+    "xs4_33",   // block
+    "xp2_7",    // blinker
+    "xs6_696",  // beehive
+    "xq4_153",  // glider
+    "xs7_2596", // loaf
+    "xs5_253",  // boat
+    "xs4_252",  // tub
+    "xs8_6996", // pond
+    "xs6_356",  // ship
+    "xp2_7e",   // toad
+    "xp2_318c", // beacon
+    "xs7_25ac", // long boat
+    "xs6_25a4", // barge
+];
+
+/// Symmetry imposed on a soup, as in apgsearch. C4 and D8 need a square soup.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default)]
+pub enum Symmetry {
+    // No symmetry.
+    #[default]
+    C1,
+    // Rotation by 180 degrees.
+    C2,
+    // Rotation by 90 degrees.
+    C4,
+    // Reflection left to right.
+    D2,
+    // Reflection left to right and top to bottom.
+    D4,
+    // All rotations and reflections.
+    D8,
+}
+
+const SYMMETRIES: &[(Symmetry, &str)] = &[
+    (Symmetry::C1, "C1"),
+    (Symmetry::C2, "C2"),
+    (Symmetry::C4, "C4"),
+    (Symmetry::D2, "D2"),
+    (Symmetry::D4, "D4"),
+    (Symmetry::D8, "D8"),
+];
+
+impl Symmetry {
+    fn needs_square(&self) -> bool {
+        matches!(self, Symmetry::C4 | Symmetry::D8)
+    }
+
+    // The cells which must match x,y in a soup of the given size.
+    fn images(&self, x: i64, y: i64, (w, h): (i64, i64)) -> Vec<(i64, i64)> {
+        let (rx, ry) = (w - 1 - x, h - 1 - y);
+        match self {
+            Symmetry::C1 => vec![(x, y)],
+            Symmetry::C2 => vec![(x, y), (rx, ry)],
+            Symmetry::C4 => vec![(x, y), (ry, x), (rx, ry), (y, rx)],
+            Symmetry::D2 => vec![(x, y), (rx, y)],
+            Symmetry::D4 => vec![(x, y), (rx, y), (x, ry), (rx, ry)],
+            Symmetry::D8 => vec![
+                (x, y),
+                (ry, x),
+                (rx, ry),
+                (y, rx),
+                (rx, y),
+                (x, ry),
+                (y, x),
+                (ry, rx),
+            ],
+        }
+    }
+}
+
+impl FromStr for Symmetry {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        SYMMETRIES
+            .iter()
+            .find(|(_, n)| n.eq_ignore_ascii_case(s.trim()))
+            .map(|(sym, _)| *sym)
+            .ok_or(anyhow!("Unknown symmetry: {s}"))
+    }
+}
+
+impl fmt::Display for Symmetry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = SYMMETRIES
+            .iter()
+            .find(|(sym, _)| sym == self)
+            .map_or("?", |(_, n)| n);
+        write!(f, "{name}")
+    }
+}
+
+/// How a soup is filled: its size, the chance of each cell being alive and its symmetry.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub struct Fill {
+    size: (i64, i64),
+    density: f64,
+    symmetry: Symmetry,
+}
+
+impl Fill {
+    pub fn new(size: (i64, i64), density: f64, symmetry: Symmetry) -> Result<Self> {
+        if size.0 <= 0 || size.1 <= 0 {
+            return Err(anyhow!("Soup size must be positive"));
+        }
+        if !(0.0..=1.0).contains(&density) {
+            return Err(anyhow!("Density must be between 0 and 1"));
+        }
+        if symmetry.needs_square() && size.0 != size.1 {
+            return Err(anyhow!("{symmetry} symmetry needs a square soup"));
+        }
+
+        Ok(Fill {
+            size,
+            density,
+            symmetry,
+        })
+    }
+
+    pub fn size(&self) -> (i64, i64) {
+        self.size
+    }
+
+    pub fn density(&self) -> f64 {
+        self.density
+    }
+
+    pub fn symmetry(&self) -> Symmetry {
+        self.symmetry
+    }
+
+    /// The live cells of the soup for the seed, filling the region from 0,0.
+    pub fn cells(&self, seed: &str) -> Vec<GridCoord> {
+        let mut rng = StdRng::seed_from_u64(hash(seed));
+
+        // Cells are decided in row order, with each copying the first of its images.
+        let mut alive: HashMap<(i64, i64), bool> = HashMap::new();
+        let mut cells = vec![];
+        for y in 0..self.size.1 {
+            for x in 0..self.size.0 {
+                let first = self
+                    .symmetry
+                    .images(x, y, self.size)
+                    .into_iter()
+                    .min_by_key(|&(x, y)| (y, x))
+                    .expect("At least one image");
+                let live = match alive.get(&first) {
+                    Some(&live) => live,
+                    None => rng.gen_bool(self.density),
+                };
+                alive.insert((x, y), live);
+
+                if live {
+                    cells.push(GridCoord::Valid(x, y));
+                }
+            }
+        }
+
+        cells
+    }
+}
+
+impl Default for Fill {
+    fn default() -> Self {
+        Fill::new(DEFAULT_SOUP_SIZE, 0.5, Symmetry::C1).expect("Valid default fill")
+    }
+}
+
+/// Parameters for a soup search.
+#[derive(Debug, Clone)]
+pub struct Search {
+    pub rule: Rule,
+    pub backend: Backend,
+    pub soups: usize,
+    pub fill: Fill,
+    // Soup i is seeded from "<seed>_<i>".
+    pub seed: String,
+    // 0 for one per core.
+    pub threads: usize,
+}
+
+/// A random seed prefix, for searches not given one.
+pub fn random_seed() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(12)
+        .map(char::from)
+        .collect()
+}
+
+/// The seed following this one: a trailing _N is incremented, otherwise _1 is appended.
+pub fn next_seed(seed: &str) -> String {
+    if let Some((prefix, n)) = seed.rsplit_once('_') {
+        if let Ok(n) = n.parse::<u64>() {
+            return format!("{}_{}", prefix, n + 1);
+        }
+    }
+
+    format!("{seed}_1")
+}
+
+// FNV-1a, so that seeds give the same soups on every platform and release.
+fn hash(seed: &str) -> u64 {
+    seed.bytes().fold(0xcbf29ce484222325, |h, b| {
+        (h ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn is_stable(populations: &[usize]) -> bool {
+    let n = populations.len();
+    if n < STABLE_WINDOW + MAX_ASH_PERIOD {
+        return false;
+    }
+
+    (1..=MAX_ASH_PERIOD)
+        .any(|p| (n - STABLE_WINDOW..n).all(|i| populations[i] == populations[i - p]))
+}
+
+/// Objects found in a search, with their counts and some seeds reproducing them.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Tally {
+    pub count: u64,
+    pub seeds: Vec<String>,
+}
+
+/// The result of a soup search.
+#[derive(Debug, Default)]
+pub struct Census {
+    pub soups: usize,
+    // Seeds of soups which had not stabilised by MAX_GENERATIONS.
+    pub unstabilised: Vec<String>,
+    pub objects: BTreeMap<String, Tally>,
+}
+
+impl Census {
+    fn record(&mut self, code: String, seed: &str) {
+        let tally = self.objects.entry(code).or_default();
+        tally.count += 1;
+        if tally.seeds.len() < MAX_SAMPLES && !tally.seeds.iter().any(|s| s == seed) {
+            tally.seeds.push(seed.to_owned());
+        }
+    }
+
+    fn merge(&mut self, other: Census) {
+        self.soups += other.soups;
+        self.unstabilised.extend(other.unstabilised);
+        for (code, tally) in other.objects {
+            let ours = self.objects.entry(code).or_default();
+            ours.count += tally.count;
+
+            // Keep the earliest seeds so the report doesn't depend on thread timing.
+            ours.seeds.extend(tally.seeds);
+            ours.seeds
+                .sort_by(|a, b| a.len().cmp(&b.len()).then(a.cmp(b)));
+            ours.seeds.truncate(MAX_SAMPLES);
+        }
+    }
+
+    /// Objects not usually found in the rule's ash, in apgcode order.
+    pub fn rare(&self, rule: &Rule) -> Vec<(&String, &Tally)> {
+        // Only Life's ash is known, everything is interesting in other rules.
+        let common: &[&str] = if *rule == Rule::default() {
+            COMMON
+        } else {
+            &[]
+        };
+        self.objects
+            .iter()
+            .filter(|(code, _)| !common.contains(&code.as_str()))
+            .collect()
+    }
+
+    /// Objects, most common first.
+    pub fn by_count(&self) -> Vec<(&String, &Tally)> {
+        let mut objects: Vec<_> = self.objects.iter().collect();
+        objects.sort_by(|a, b| b.1.count.cmp(&a.1.count).then(a.0.cmp(b.0)));
+        objects
+    }
+
+    pub fn write_report(&self, out: &mut dyn Write, search: &Search) -> Result<()> {
+        writeln!(out, "# Census of {} soups", self.soups)?;
+        writeln!(out, "# Rule: {}", search.rule)?;
+        let (w, h) = search.fill.size();
+        writeln!(out, "# Soup size: {w}x{h}")?;
+        writeln!(out, "# Density: {}", search.fill.density())?;
+        writeln!(out, "# Symmetry: {}", search.fill.symmetry())?;
+        writeln!(
+            out,
+            "# Seeds: {}_0 to {}_{}",
+            search.seed,
+            search.seed,
+            search.soups.saturating_sub(1)
+        )?;
+        writeln!(out, "# Unstabilised soups: {}", self.unstabilised.len())?;
+        for seed in &self.unstabilised {
+            writeln!(out, "#   {seed}")?;
+        }
+        writeln!(out, "#")?;
+        writeln!(out, "# apgcode count seeds")?;
+        for (code, tally) in self.by_count() {
+            writeln!(out, "{} {} {}", code, tally.count, tally.seeds.join(","))?;
+        }
+
+        let rare = self.rare(&search.rule);
+        if !rare.is_empty() {
+            writeln!(out, "#")?;
+            writeln!(out, "# Rare objects")?;
+            for (code, tally) in rare {
+                writeln!(out, "# {} {} {}", code, tally.count, tally.seeds.join(","))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Searches soups on one thread, taking soup numbers from next until all are done.
+struct Searcher<'a> {
+    search: &'a Search,
+    census: Census,
+    // Canonical code -> apgcode, as classifying is much slower than encoding.
+    names: HashMap<String, String>,
+}
+
+impl Searcher<'_> {
+    fn run_soup(&mut self, seed: &str) {
+        let mut universe = self.search.backend.create(self.search.rule);
+        for c in self.search.fill.cells(seed) {
+            universe.set(c);
+        }
+
+        let mut populations = vec![universe.stats().population];
+        while !is_stable(&populations) {
+            if universe.generation() >= MAX_GENERATIONS {
+                warn!(
+                    "Soup {} not stabilised after {} generations",
+                    seed, MAX_GENERATIONS
+                );
+                self.census.unstabilised.push(seed.to_owned());
+                return;
+            }
+
+            populations.push(universe.update().population);
+        }
+
+        let rule = self.search.rule;
+        let ash = universe.live_cells();
+        for object in separate_independent(&ash, rule, &Separation::default()) {
+            let key = canonical(&object);
+            let code = self
+                .names
+                .entry(key)
+                .or_insert_with(|| apgcode(&object, rule, MAX_OBJECT_PERIOD))
+                .clone();
+
+            // Sparks, e.g. from passing spaceships, die out on their own.
+            if code != "xs0_0" {
+                self.census.record(code, seed);
+            }
+        }
+    }
+}
+
+/// Run the search over all threads, returning the combined census.
+pub fn search(search: &Search) -> Result<Census> {
+    let threads = match search.threads {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    };
+    info!("Searching {} soups on {} threads", search.soups, threads);
+
+    let next = AtomicUsize::new(0);
+    let censuses: Vec<Census> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut searcher = Searcher {
+                        search,
+                        census: Census::default(),
+                        names: HashMap::new(),
+                    };
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        if i >= search.soups {
+                            break;
+                        }
+                        if i > 0 && i.is_multiple_of(1000) {
+                            info!("Searched {} soups", i);
+                        }
+
+                        searcher.run_soup(&format!("{}_{}", search.seed, i));
+                        searcher.census.soups += 1;
+                    }
+                    searcher.census
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .map(|w| w.join().expect("Search thread panicked"))
+            .collect()
+    });
+
+    let mut census = Census::default();
+    for c in censuses {
+        census.merge(c);
+    }
+    census.unstabilised.sort();
+
+    Ok(census)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashSet;
+
+    fn code(rows: &[&str]) -> String {
+        let mut cells = vec![];
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                if c == 'o' {
+                    cells.push(GridCoord::Valid(x as i64, y as i64));
+                }
+            }
+        }
+        apgcode(&cells, Rule::default(), MAX_OBJECT_PERIOD)
+    }
+
+    #[test]
+    fn common_codes() {
+        let common = [
+            code(&[".oo.", "o..o", "o..o", ".oo."]),
+            code(&["oo.", "o.o", ".oo"]),
+            code(&[".ooo", "ooo."]),
+            code(&["oo..", "oo..", "..oo", "..oo"]),
+            code(&["oo..", "o.o.", ".o.o", "..o."]),
+            code(&[".o..", "o.o.", ".o.o", "..o."]),
+        ];
+        for c in common {
+            assert!(COMMON.contains(&c.as_str()), "{c} not common");
+        }
+    }
+
+    #[test]
+    fn soups_are_reproducible() -> Result<()> {
+        let fill = Fill::default();
+        assert_eq!(fill.cells("abc_1"), fill.cells("abc_1"));
+        assert_ne!(fill.cells("abc_1"), fill.cells("abc_2"));
+
+        let fill = Fill::new((4, 3), 0.5, Symmetry::C1)?;
+        assert!(fill
+            .cells("abc_1")
+            .iter()
+            .all(|c| matches!(*c, GridCoord::Valid(0..=3, 0..=2))));
+
+        assert!(Fill::new((0, 3), 0.5, Symmetry::C1).is_err());
+        assert!(Fill::new((4, 3), 1.5, Symmetry::C1).is_err());
+        assert!(Fill::new((4, 3), 0.5, Symmetry::C4).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn density() -> Result<()> {
+        assert!(Fill::new((10, 10), 0.0, Symmetry::C1)?
+            .cells("x")
+            .is_empty());
+        assert_eq!(
+            Fill::new((10, 10), 1.0, Symmetry::D8)?.cells("x").len(),
+            100
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn symmetric_soups() -> Result<()> {
+        for (symmetry, size) in [
+            (Symmetry::C2, (7, 5)),
+            (Symmetry::C4, (6, 6)),
+            (Symmetry::D2, (7, 5)),
+            (Symmetry::D4, (6, 5)),
+            (Symmetry::D8, (7, 7)),
+        ] {
+            let fill = Fill::new(size, 0.5, symmetry)?;
+            let cells: HashSet<GridCoord> = fill.cells("sym").into_iter().collect();
+            for c in &cells {
+                let GridCoord::Valid(x, y) = *c else {
+                    panic!("Invalid cell");
+                };
+                for (ix, iy) in symmetry.images(x, y, size) {
+                    assert!(cells.contains(&GridCoord::Valid(ix, iy)), "{symmetry}");
+                }
+            }
+        }
+
+        assert_eq!("d4".parse::<Symmetry>()?, Symmetry::D4);
+        assert!("D3".parse::<Symmetry>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn next_seeds() {
+        assert_eq!(next_seed("abc_9"), "abc_10");
+        assert_eq!(next_seed("abc"), "abc_1");
+        assert_eq!(next_seed("a_b"), "a_b_1");
+    }
+
+    #[test]
+    fn census() -> Result<()> {
+        let search = Search {
+            rule: Rule::default(),
+            backend: Backend::default(),
+            soups: 20,
+            fill: Fill::new((8, 8), 0.5, Symmetry::C1)?,
+            seed: "test".to_owned(),
+            threads: 2,
+        };
+        let census = super::search(&search)?;
+
+        assert_eq!(census.soups, 20);
+        assert!(census
+            .objects
+            .values()
+            .all(|t| t.count > 0 && !t.seeds.is_empty()));
+
+        // The same seeds give the same census.
+        assert_eq!(census.objects, super::search(&search)?.objects);
+
+        let mut report = vec![];
+        census.write_report(&mut report, &search)?;
+        let report = String::from_utf8(report)?;
+        assert!(report.starts_with("# Census of 20 soups\n"));
+
+        Ok(())
+    }
+}
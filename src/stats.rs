@@ -0,0 +1,166 @@
+//! Recording statistics for each generation.
+
+use anyhow::{Context, Result};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::time::Duration;
+
+use crate::grid::Life;
+
+/// Statistics for one generation.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default)]
+pub struct Sample {
+    pub generation: usize,
+    pub population: usize,
+    pub births: usize,
+    pub deaths: usize,
+    // Bounding box size, 0x0 when empty.
+    pub width: i64,
+    pub height: i64,
+    // Time taken to compute the generation.
+    pub update: Duration,
+    // Time taken to draw the generation, if it was drawn.
+    pub draw: Duration,
+}
+
+/// The most recent generations' statistics.
+pub struct History {
+    capacity: usize,
+    samples: VecDeque<Sample>,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        History {
+            capacity,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Start again from the universe's current generation, e.g. after it has been edited.
+    pub fn reset(&mut self, universe: &dyn Life) {
+        self.samples.clear();
+        self.record(universe, Duration::ZERO);
+
+        // The universe's births and deaths are from before the edit.
+        if let Some(first) = self.samples.back_mut() {
+            first.births = 0;
+            first.deaths = 0;
+        }
+    }
+
+    /// Record the universe's current generation, which took `update` to compute.
+    pub fn record(&mut self, universe: &dyn Life, update: Duration) {
+        let stats = universe.stats();
+        let (width, height) = stats.bounds.map_or((0, 0), |b| (b.width(), b.height()));
+
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(Sample {
+            generation: universe.generation(),
+            population: stats.population,
+            births: stats.births,
+            deaths: stats.deaths,
+            width,
+            height,
+            update,
+            draw: Duration::ZERO,
+        });
+    }
+
+    /// Record the time taken to draw the latest generation.
+    pub fn record_draw(&mut self, draw: Duration) {
+        if let Some(latest) = self.samples.back_mut() {
+            latest.draw = draw;
+        }
+    }
+
+    pub fn samples(&self) -> &VecDeque<Sample> {
+        &self.samples
+    }
+
+    pub fn latest(&self) -> Option<&Sample> {
+        self.samples.back()
+    }
+
+    pub fn write_csv(&self, out: &mut dyn Write) -> Result<()> {
+        writeln!(
+            out,
+            "generation,population,births,deaths,width,height,update_ms,draw_ms"
+        )?;
+        for s in &self.samples {
+            writeln!(
+                out,
+                "{},{},{},{},{},{},{:.3},{:.3}",
+                s.generation,
+                s.population,
+                s.births,
+                s.deaths,
+                s.width,
+                s.height,
+                s.update.as_secs_f64() * 1000.0,
+                s.draw.as_secs_f64() * 1000.0
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn save_csv(&self, filename: &str) -> Result<()> {
+        let file = File::create(filename).with_context(|| format!("Creating {filename}"))?;
+        let mut out = BufWriter::new(file);
+        self.write_csv(&mut out)?;
+        out.flush()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::grid::{GridCoord, Universe};
+
+    #[test]
+    fn births_and_deaths() -> Result<()> {
+        let mut universe = Universe::new();
+        for y in 0..3 {
+            universe.grid.set(GridCoord::Valid(0, y));
+        }
+
+        let mut history = History::new(2);
+        history.reset(&universe);
+        universe.update();
+        history.record(&universe, Duration::from_millis(2));
+
+        let latest = history.latest().expect("Recorded");
+        assert_eq!(
+            (
+                latest.generation,
+                latest.population,
+                latest.births,
+                latest.deaths
+            ),
+            (1, 3, 2, 2)
+        );
+        assert_eq!((latest.width, latest.height), (3, 1));
+
+        // Only the latest samples are kept.
+        universe.update();
+        history.record(&universe, Duration::ZERO);
+        assert_eq!(history.samples().len(), 2);
+        assert_eq!(history.samples()[0].generation, 1);
+
+        let mut csv = vec![];
+        history.write_csv(&mut csv)?;
+        let csv = String::from_utf8(csv)?;
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1], "1,3,2,2,3,1,2.000,0.000");
+
+        Ok(())
+    }
+}